@@ -1,27 +1,95 @@
-use crate::discogs_client::{authenticate, UserData, make_auth_request};
-use crate::record::{load_collection, Record, RecordCollection};
-
-use crossterm::{cursor, event, execute, style::Stylize, terminal};
+use crate::capabilities::{ColorSupport, GraphicsProtocol};
+use crate::discogs_client::{
+    add_to_collection, authenticate, get_collection, get_release, get_username, master_versions,
+    remove_from_collection, search, CollectionInstance, CollectionItem, DiscogsRef, ResultKind,
+    SearchResult, UserData,
+};
+use crate::export::{export_card, ExportFormat};
+use crate::filter::Filter;
+use crate::graphics::{clear_cover, hide_cover, CoverImage};
+use crate::img_to_ascii::{AsciiArt, RenderMode, ADJUSTMENTS, ADJUSTMENTS_HELP};
+use crate::layout::{
+    border, boxed, boxed_centered, boxed_highlighted, Layout, Rect, BOTTOM, DIVIDER, MIN_COLS,
+    MIN_ROWS, TOP,
+};
+use crate::line_editor::{suggestions, LineEditor};
+use crate::picker::Picker;
+use crate::record::{cover_url, label_names, Record, RecordCollection, COVER_ART_SIZE};
+use crate::settings::{parse_auto, parse_switch, value_names, Settings, SWITCH_NAMES};
+use crate::sort::{sort_name, sort_records, Grouping, SortKey, SortOrder};
+use crate::storage::{
+    format_timestamp, list_backups, load_collection, load_removed_instances, restore_backup,
+    save_collection, save_removed_instances,
+};
+
+use crossterm::event::KeyCode;
+use crossterm::style::{Print, Stylize};
+use crossterm::{cursor, execute, queue, terminal};
 use reqwest::blocking::Client;
-use std::io::{stdout, Result};
+use std::collections::HashSet;
+use std::io::{stdout, Result, Write};
 use std::path::Path;
-
-const APP_COLS: u16 = 130;
-const APP_ROWS: u16 = 40;
+use uuid::Uuid;
+
+// The commands that can be entered in command mode
+const COMMANDS: [&str; 11] = [
+    "Login",
+    "Add",
+    "AddRelease",
+    "Import",
+    "Sync",
+    "Set",
+    "Remove",
+    "Restore",
+    "Regenerate",
+    "Adjust",
+    "Export",
+];
+// The names of the settings changed with the Set command, other than the adjustments
+const SETTINGS: [&str; 6] = ["sync", "render", "color", "graphics", "sort", "group"];
 // Path to user's collection data
 const USER_DATA_PATH: &str = "data/user_data.json";
+// The number of records imported between saves of the collection
+const IMPORT_SAVE_INTERVAL: usize = 25;
 
 // user_data: The user's Discogs authentication keys
 // client: A blocking HTTP client to make requests to the Discogs API
 // selected: The index of the currently selected record
+// list_offset: The index of the first record shown in the records list, which scrolls to keep the
+//              selected record visible
+// on_header: Whether the header of the selected record's group is selected rather than the record,
+//            when the list is grouped. The selected record is then the group's first
+// collapsed: The keys of the groups whose records are hidden under their header
+// filter: The filter narrowing the records list, if the user has opened one with '/'
 // collection: The user's record collection data
-// sorted_titles: The collection's (artist, title) pair sorted as is displayed in the app
+// sorted_ids: The collection's record IDs sorted as is displayed in the app
+// settings: The user's application settings
+// username: The logged in user's Discogs username, retrieved the first time it's needed
+// modified: Whether the collection has changed since it was last saved
+// cover_art: The selected record's cover drawn as an image or as text art, along with the
+//            record's ID
+// editor: Reads the commands typed in command mode, keeping their history
+// detected_colors: The colors the terminal supports, used unless the settings override them
+// detected_graphics: The graphics protocol the terminal supports, used unless the settings
+//                    override it
 pub struct App {
     user_data: Option<UserData>,
+    username: Option<String>,
+    settings: Settings,
     pub(crate) client: Client,
     selected: usize,
+    list_offset: usize,
+    on_header: bool,
+    collapsed: HashSet<String>,
+    filter: Option<Filter>,
     collection: RecordCollection,
-    sorted_titles: Vec<(String, String)>,
+    sorted_ids: Vec<Uuid>,
+    removed_instances: Vec<CollectionInstance>,
+    modified: bool,
+    cover_art: Option<(Uuid, CoverArt)>,
+    editor: LineEditor,
+    detected_colors: ColorSupport,
+    detected_graphics: GraphicsProtocol,
 }
 
 impl App {
@@ -34,34 +102,46 @@ impl App {
         }
 
         let collection = load_collection()?;
-
-        // Create a vector of sorted titles from the collection that can be quickly referenced
-        let sorted_titles: Vec<&(String, String)> = collection.keys().collect();
-        let mut sorted_titles: Vec<(String, String)> =
-            sorted_titles.into_iter().map(|k| k.clone()).collect();
-        sorted_titles.sort();
+        let settings = Settings::load()?;
+
+        // Create a vector of sorted IDs from the collection that can be quickly referenced
+        let mut sorted_ids: Vec<Uuid> = collection.keys().copied().collect();
+        sort_records(
+            &collection,
+            &mut sorted_ids,
+            &settings.sort,
+            settings.grouping,
+        );
 
         // Raw mode changes the terminal's behavior
         // For example, ignores Ctrl-C and doesn't write keyboard input to the terminal
         terminal::enable_raw_mode()?;
 
-        // Resizes the terminal to the app's size (130 x 40) 
-        let old_term_size = terminal::size()?;
-        execute!(stdout(), terminal::SetSize(APP_COLS, APP_ROWS))?;
-
-        // Pauses execution until terminal is resized
-        if old_term_size != (APP_COLS, APP_ROWS) {
-            wait_for_resize()?;
-        }
-
-        execute!(stdout(), cursor::Hide)?;
+        // The app is drawn over the whole terminal, laid out to fit its size
+        execute!(
+            stdout(),
+            terminal::Clear(terminal::ClearType::All),
+            cursor::Hide
+        )?;
 
         Ok(App {
             user_data,
+            username: None,
+            settings,
             client: Client::new(),
             selected: 0,
+            list_offset: 0,
+            on_header: false,
+            collapsed: HashSet::new(),
+            filter: None,
             collection,
-            sorted_titles,
+            sorted_ids,
+            removed_instances: load_removed_instances()?,
+            modified: false,
+            cover_art: None,
+            editor: LineEditor::load(),
+            detected_colors: ColorSupport::detect(),
+            detected_graphics: GraphicsProtocol::detect(),
         })
     }
 
@@ -69,31 +149,48 @@ impl App {
         use crossterm::event::{
             read,
             Event::{Key, Resize},
-            KeyCode, KeyEvent,
+            KeyEvent,
         };
 
         // The main run loop
         loop {
+            self.update_cover_art()?;
+            self.scroll_list()?;
             self.print()?;
+            let editing = self.filter.as_ref().is_some_and(|filter| filter.editing);
             match read()? {
+                // Keys are typed into the filter's query while it's being edited
+                Key(KeyEvent { code, .. }) if editing => self.edit_filter(code)?,
                 Key(KeyEvent { code, .. }) => match code {
                     KeyCode::Char('q') => break,
-                    KeyCode::Char('c') => {
+                    // Commands aren't available while the terminal is too small to draw them
+                    KeyCode::Char('c') if Layout::current()?.fits() => {
                         self.command_mode()?;
                     }
-                    // Moves selection up and down, within 0..collection.len() bounds
-                    KeyCode::Up => self.selected = self.selected.saturating_sub(1),
-                    KeyCode::Down => {
-                        self.selected = (self.selected + 1).min(self.collection.len() - 1)
+                    // Opens the filter, or goes back to editing its query if it's already open
+                    KeyCode::Char('/') => match &mut self.filter {
+                        Some(filter) => filter.editing = true,
+                        None => self.filter = Some(Filter::new()),
+                    },
+                    // Closes the filter, listing every record again
+                    KeyCode::Esc => self.filter = None,
+                    // Enter and Space collapse or expand the selected group, while Left collapses
+                    // the group of the selected record and Right expands it
+                    KeyCode::Enter | KeyCode::Char(' ') => self.toggle_group(None),
+                    KeyCode::Left => self.toggle_group(Some(true)),
+                    KeyCode::Right => self.toggle_group(Some(false)),
+                    // Any other letter or digit jumps to the next artist starting with it. C and
+                    // Q are taken by the keys above, so they're jumped to with Shift
+                    KeyCode::Char(key) if key.is_ascii_alphanumeric() && key != 'c' => {
+                        self.jump_to(key)
                     }
-                    _ => (),
+                    code => self.move_selection(code)?,
                 },
-                // Prevents user from resizing app since printing is dependent on a set size
-                // Resets the terminal to the application size when the user resizes it
-                // Doesn't work when full screen, or sticky to the side of the screen
+                // The app is laid out again to fit the terminal's new size, and the cover is
+                // prepared again for its pane's new size
                 Resize(..) => {
-                    execute!(stdout(), terminal::SetSize(APP_COLS, APP_ROWS))?;
-                    wait_for_resize()?;
+                    execute!(stdout(), terminal::Clear(terminal::ClearType::All))?;
+                    self.cover_art = None;
                 }
                 _ => (),
             }
@@ -102,172 +199,515 @@ impl App {
     }
 
     fn print(&self) -> Result<()> {
-        execute!(stdout(), cursor::MoveTo(0, 1))?;
+        let layout = Layout::current()?;
+        let shown = self.shown();
+        let entries = self.list_entries(&shown);
+        if !layout.fits() {
+            return print_too_small(&layout);
+        }
+
         // Print Header
-        print!("╔════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╗\r\n");
-        print!("║                                                        Vinylla - v0.1.0                                                        ║\r\n");
-        print!("╚════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-        
+        let header = layout.header;
+        let title = format!("Vinylla - v{}", env!("CARGO_PKG_VERSION"));
+        print_at((header.x, header.y), &border(header.width, TOP))?;
+        print_at(
+            (header.x, header.y + 1),
+            &boxed_centered(&title, header.width),
+        )?;
+        print_at((header.x, header.y + 2), &border(header.width, BOTTOM))?;
+
         // Print Contents
-        self.print_content(APP_ROWS - 1 as u16 - 6)?;
+        self.print_content(&layout, &entries)?;
 
         // Print Footer
-        execute!(stdout(), cursor::MoveTo(0, 37))?;
-        print!("╔════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╗\r\n");
-        print!("║ {}ommand:                                                                                                                       ║\r\n",
-            "C".underlined()
-        );
-        print!("╚════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-        Ok(())
+        // The footer shows the filter's query in place of the command prompt while the list is
+        // filtered, along with how many records match it
+        let footer = layout.footer;
+        let footer_line = match &self.filter {
+            Some(filter) => {
+                let hint = format!(
+                    "{} of {} records   Esc: Clear",
+                    shown.len(),
+                    self.sorted_ids.len()
+                );
+                let query_width =
+                    (footer.width as usize).saturating_sub(4 + 1 + hint.chars().count());
+                boxed(
+                    &format!("/{:query_width$}{}", filter.query, hint),
+                    footer.width,
+                )
+            }
+            None => format!(
+                "║ {}ommand:{:width$} ║",
+                "C".underlined(),
+                "",
+                width = footer.width.saturating_sub(12) as usize
+            ),
+        };
+        print_at((footer.x, footer.y), &border(footer.width, TOP))?;
+        print_at((footer.x, footer.y + 1), &footer_line)?;
+        print_at((footer.x, footer.y + 2), &border(footer.width, BOTTOM))?;
+
+        stdout().flush()?;
+
+        // The cursor is shown after the filter's query while it's being edited
+        match &self.filter {
+            Some(filter) if filter.editing => execute!(
+                stdout(),
+                cursor::MoveTo(
+                    footer.x + 3 + filter.query.chars().count() as u16,
+                    footer.y + 1
+                ),
+                cursor::Show
+            ),
+            _ => execute!(stdout(), cursor::Hide),
+        }
     }
 
-    fn print_content(&self, rows: u16) -> Result<()> {
-        execute!(stdout(), cursor::MoveTo(0, 4))?;
-        print!("╔══════════════════════════════════════╗ ╔═══════════════════════════════════════════════════════════════════════════════════════╗\r\n");
-        
-        // Gets the artist and title of the selected record to display at the info header
-        let title_str = if self.sorted_titles.len() != 0 {
-            format!(
-                "{} - {}",
-                self.sorted_titles[self.selected].0,
-                self.sorted_titles[self.selected].1
-            )
-        } else {
-            "".to_string()
-        };
-        
+    fn print_content(&self, layout: &Layout, entries: &[ListEntry]) -> Result<()> {
         // Holds the information of the currently selected record
-        // record is None if there aren't any records in the collection
-        let record = if self.sorted_titles.len() != 0 {
-            Some(
-                self.collection
-                    .get(&self.sorted_titles[self.selected])
-                    .unwrap(),
-            )
-        } else {
-            None
-        };
-
-        print!(
-            "║              My Records              ║ ║ {:^85} ║\r\n",
-            title_str
-        );
-        print!("╟──────────────────────────────────────╢ ╟───────────────────────────────────────────────────────────────────────────────────────╢\r\n");
-
-        // Is set to true once the iterator reaches the end of the collection
-        let mut reached_end = self.sorted_titles.len() == 0;
-        // Prints the content section row by row
-        for i in 1..(rows - 3) {
-            execute!(stdout(), cursor::MoveTo(0, 6 + i))?;
-            // Prints the left section with the records listing
-            if !reached_end {
-                // record_str holds the string for a record in the listing on the left
-                // TODO: handle printing when selected record's index is greater than then number
-                //       of rows
-                let mut record_str = if self.selected == i as usize - 1 {
-                    format!("> {}. ", i)
-                } else {
-                    format!("  {}. ", i)
-                };
+        // record is None if there aren't any records in the collection, none match the filter, or
+        // a group's header is selected
+        let record = self
+            .selected_id()
+            .filter(|_| !entries.is_empty())
+            .map(|id| &self.collection[&id]);
+
+        // Prints the records listing on the left
+        let list = layout.list;
+        print_at((list.x, list.y), &border(list.width, TOP))?;
+        print_at(
+            (list.x, list.y + 1),
+            &boxed_centered("My Records", list.width),
+        )?;
+        print_at((list.x, list.y + 2), &border(list.width, DIVIDER))?;
+        for (i, row) in (list.y + 3..list.bottom() - 1).enumerate() {
+            // record_str holds the string for a record in the listing, which keeps its number
+            // in the whole list when the list is filtered
+            let record_str = match entries.get(self.list_offset + i) {
+                // Group headers show whether they're collapsed and how many records they hold
+                Some(ListEntry::Group {
+                    name,
+                    first,
+                    count,
+                    collapsed,
+                    ..
+                }) => {
+                    let group_str = format!(
+                        "{} {} {} ({})",
+                        if self.on_header && self.selected == *first {
+                            '>'
+                        } else {
+                            ' '
+                        },
+                        if *collapsed { '▸' } else { '▾' },
+                        name,
+                        count
+                    );
+                    boxed(&group_str, list.width)
+                }
+                Some(&ListEntry::Record(i)) => {
+                    let selected = !self.on_header && self.selected == i;
+                    let prefix = format!("{} {}. ", if selected { '>' } else { ' ' }, i + 1);
+                    let title = self.collection[&self.sorted_ids[i]].display_title();
+                    // Highlights the characters of the title that matched the filter
+                    let highlights: Vec<usize> = match &self.filter {
+                        Some(filter) => filter
+                            .highlights(&title)
+                            .into_iter()
+                            .map(|position| position + prefix.chars().count())
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    boxed_highlighted(&(prefix + &title), &highlights, list.width)
+                }
+                None => boxed("", list.width),
+            };
+            print_at((list.x, row), &record_str)?;
+        }
+        print_at((list.x, list.bottom() - 1), &border(list.width, BOTTOM))?;
+        self.print_scrollbar(layout, entries.len())?;
+
+        // Prints the info section on the right, headed by the artist and title of the selected
+        // record, or the name of the selected group
+        if let Some(info) = layout.info {
+            let title_str = match record {
+                Some(record) => record.display_title(),
+                None if self.on_header => self.selected_group().unwrap_or_default(),
+                None => String::new(),
+            };
+            print_at((info.x, info.y), &border(info.width, TOP))?;
+            print_at(
+                (info.x, info.y + 1),
+                &boxed_centered(&title_str, info.width),
+            )?;
+            print_at((info.x, info.y + 2), &border(info.width, DIVIDER))?;
+            for row in info.y + 3..info.bottom() - 1 {
+                print_at((info.x, row), &boxed("", info.width))?;
+            }
+            print_at((info.x, info.bottom() - 1), &border(info.width, BOTTOM))?;
+        }
+        if let (Some(record), Some(details)) = (record, layout.details) {
+            print_details(details, record)?;
+        }
+        stdout().flush()?;
 
-                // Appends the record artist and title to the string
-                match self.sorted_titles.get(i as usize - 1) {
-                    Some((artist, title)) => {
-                        record_str.push_str(artist.as_str());
-                        record_str.push_str(" - ");
-                        record_str.push_str(title.as_str());
-                    }
-                    None => {
-                        reached_end = true;
-                        record_str = String::new();
+        // Print the selected record's album cover
+        match (record, layout.cover) {
+            (Some(record), Some(cover)) => match &self.cover_art {
+                Some((id, CoverArt::Image(image))) if *id == record.id => {
+                    image.draw((cover.x, cover.y))?
+                }
+                Some((id, CoverArt::Text(art))) if *id == record.id => {
+                    hide_cover(self.graphics())?;
+                    art.print_at((cover.x, cover.y), self.colors())?
+                }
+                // Records without a cached cover are drawn with the art saved in the collection,
+                // centered in the cover's pane when it fits
+                _ => {
+                    hide_cover(self.graphics())?;
+                    let art = &record.image;
+                    if art.width() <= cover.width as usize && art.height() <= cover.height as usize
+                    {
+                        let position = (
+                            cover.x + (cover.width - art.width() as u16) / 2,
+                            cover.y + (cover.height - art.height() as u16) / 2,
+                        );
+                        art.print_at(position, self.colors())?
                     }
                 }
-                // Truncates the string to be within the sections bounds
-                max_len(&mut record_str, 35);
+            },
+            // Hides the cover image of the last record that was removed, or when the terminal has
+            // become too small for it
+            _ => hide_cover(self.graphics())?,
+        }
 
-                print!("║{:37} ║ ", record_str);
-            } else {
-                print!("║  {:35} ║ ", "");
+        Ok(())
+    }
+
+    // Draws a scrollbar over the records list's right border when the collection doesn't fit in
+    // it, with a thumb as long as the share of the records shown and placed where they are
+    fn print_scrollbar(&self, layout: &Layout, total: usize) -> Result<()> {
+        let rows = list_rows(layout);
+        if total <= rows {
+            return Ok(());
+        }
+
+        let thumb_rows = (rows * rows / total).max(1);
+        let thumb_start = self.list_offset * (rows - thumb_rows) / (total - rows);
+        let x = layout.list.right() - 1;
+        for row in thumb_start..thumb_start + thumb_rows {
+            print_at((x, layout.list.y + 3 + row as u16), "█")?;
+        }
+
+        Ok(())
+    }
+
+    // The indices into sorted_ids of the records shown in the list, which are the ones matching
+    // the filter while the list is filtered
+    fn shown(&self) -> Vec<usize> {
+        let records = 0..self.sorted_ids.len();
+        match &self.filter {
+            Some(filter) => records
+                .filter(|i| filter.matches(&self.collection[&self.sorted_ids[*i]]))
+                .collect(),
+            None => records.collect(),
+        }
+    }
+
+    // The rows of the records list, which are the shown records under their groups' headers when
+    // the list is grouped. Groups are always expanded while the list is filtered, so that every
+    // match can be seen
+    fn list_entries(&self, shown: &[usize]) -> Vec<ListEntry> {
+        let grouping = match self.settings.grouping {
+            Some(grouping) => grouping,
+            None => return shown.iter().map(|i| ListEntry::Record(*i)).collect(),
+        };
+        let record = |i: &usize| &self.collection[&self.sorted_ids[*i]];
+        let key = |i: &usize| grouping.key(record(i));
+
+        // Each group is named after its first record, e.g. an artist written differently by
+        // other records is shown as the first record writes it
+        let mut entries = Vec::new();
+        for records in shown.chunk_by(|a, b| key(a) == key(b)) {
+            let key = key(&records[0]);
+            let collapsed = self.filter.is_none() && self.collapsed.contains(&key);
+            entries.push(ListEntry::Group {
+                name: grouping.group(record(&records[0])),
+                key,
+                first: records[0],
+                count: records.len(),
+                collapsed,
+            });
+            if !collapsed {
+                entries.extend(records.iter().map(|i| ListEntry::Record(*i)));
             }
+        }
 
-            // If a record is selected (collection is not empty) print a row of the info section
-            match record {
-                Some(record) => self.print_info_row(i, &record),
-                // Otherwise print a blank row
-                None => print!("║ {:^85} ║\r\n", ""),
+        entries
+    }
+
+    // The position in the list of the selected record or group header
+    fn cursor(&self, entries: &[ListEntry]) -> Option<usize> {
+        entries.iter().position(|entry| match entry {
+            ListEntry::Group { first, .. } => self.on_header && *first == self.selected,
+            ListEntry::Record(i) => !self.on_header && *i == self.selected,
+        })
+    }
+
+    // Selects a row of the list, where selecting a group's header selects its first record
+    fn select_entry(&mut self, entry: &ListEntry) {
+        match entry {
+            ListEntry::Group { first, .. } => {
+                self.selected = *first;
+                self.on_header = true;
+            }
+            ListEntry::Record(i) => {
+                self.selected = *i;
+                self.on_header = false;
             }
         }
+    }
 
-        execute!(stdout(), cursor::MoveTo(0, 3 + rows))?;
-        print!("╚══════════════════════════════════════╝ ╚═══════════════════════════════════════════════════════════════════════════════════════╝\r\n");
+    // The ID of the selected record, or None if there are no records or a group's header is
+    // selected
+    fn selected_id(&self) -> Option<Uuid> {
+        if self.on_header {
+            None
+        } else {
+            self.sorted_ids.get(self.selected).copied()
+        }
+    }
 
-        // Print the selected record's album cover and tracklist 
-        match record {
-            Some(record) => {
-                record.image.print_at((82, 8))?;
-                print_tracklist(20, record)?;
+    // The key of the group the selected record is listed in, if the list is grouped
+    fn selected_group(&self) -> Option<String> {
+        let grouping = self.settings.grouping?;
+        let id = self.sorted_ids.get(self.selected)?;
+        Some(grouping.key(&self.collection[id]))
+    }
+
+    // Collapses or expands the selected record's group, or toggles it when collapse is None
+    // Collapsing a group selects its header, and groups can only be toggled from their header.
+    // Groups aren't collapsed while the list is filtered
+    fn toggle_group(&mut self, collapse: Option<bool>) {
+        let key = match self.selected_group() {
+            Some(key) if self.filter.is_none() => key,
+            _ => return,
+        };
+        let collapse = match collapse {
+            Some(collapse) => collapse,
+            None if self.on_header => !self.collapsed.contains(&key),
+            None => return,
+        };
+
+        if collapse {
+            self.collapsed.insert(key);
+            self.on_header = true;
+        } else {
+            self.collapsed.remove(&key);
+        }
+    }
+
+    // Scrolls the records list so that the selected record is visible, e.g. after it's moved
+    // past the list's last row or the terminal's been made shorter
+    // A record that's hidden in a collapsed group has its group's header selected instead, and
+    // when the filter no longer matches the selected record, the first row is selected. A filter
+    // that's no longer being edited is closed once nothing matches it, e.g. after its last match
+    // is removed
+    fn scroll_list(&mut self) -> Result<()> {
+        if self.filter.as_ref().is_some_and(|filter| !filter.editing) && self.shown().is_empty() {
+            self.filter = None;
+        }
+        if self.settings.grouping.is_none() {
+            self.on_header = false;
+        }
+
+        let entries = self.list_entries(&self.shown());
+        let position = match self.cursor(&entries) {
+            Some(position) => position,
+            None => {
+                let group = self.selected_group();
+                let is_group = |entry: &ListEntry| match entry {
+                    ListEntry::Group { key, .. } => Some(key) == group.as_ref(),
+                    ListEntry::Record(_) => false,
+                };
+                let position = entries.iter().position(is_group).unwrap_or(0);
+                if let Some(entry) = entries.get(position) {
+                    self.select_entry(entry);
+                }
+                position
             }
-            None => (),
+        };
+
+        let rows = list_rows(&Layout::current()?);
+        if position < self.list_offset {
+            self.list_offset = position;
+        } else if position >= self.list_offset + rows {
+            self.list_offset = position + 1 - rows;
         }
 
+        // Doesn't leave blank rows at the end of the list when there are records above it
+        self.list_offset = self.list_offset.min(entries.len().saturating_sub(rows));
+
         Ok(())
     }
 
-    // Takes the selected record and a row to print and prints the corresponding information
-    fn print_info_row(&self, i: u16, record: &Record) {
-        match i {
-            2 => print!(
-                "║   {:9}{:<24}   {:41}  ║\r\n",
-                "Release:",
-                record.year.to_string(),
-                ""
-            ),
-            4 => print!(
-                "║   {:9}{:<24}   {:41}  ║\r\n",
-                "Genre:",
-                max_len(&mut record.genre.join(" / "), 24),
-                ""
-            ),
-            6 => print!(
-                "║   {:9}{:<24}   {:41}  ║\r\n",
-                "Style:",
-                max_len(&mut record.style.join(" / "), 24),
-                ""
-            ),
-            8 => print!(
-                "║   {:9}{:<24}   {:41}  ║\r\n",
-                "Country:", record.country, ""
-            ),
-            10 => print!(
-                "║   {:9}{:<24}   {:41}  ║\r\n",
-                "Format:", record.format, ""
-            ),
-            12 => print!("║   {:^34}   {:41}  ║\r\n", "Tracklist", ""),
-            13 => print!("║   {:^34}   {:41}  ║\r\n", "─────────────────────", ""),
-            _ => print!("║ {:^85} ║\r\n", ""),
+    // Moves the selection through the rows of the list, by one row with the arrow keys, by a page
+    // of the list with Page Up and Page Down, or to either end of it with Home and End
+    fn move_selection(&mut self, code: KeyCode) -> Result<()> {
+        let entries = self.list_entries(&self.shown());
+        let position = match self.cursor(&entries) {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+        let last = entries.len() - 1;
+        let page = list_rows(&Layout::current()?);
+
+        let position = match code {
+            KeyCode::Up => position.saturating_sub(1),
+            KeyCode::Down => (position + 1).min(last),
+            KeyCode::PageUp => position.saturating_sub(page),
+            KeyCode::PageDown => (position + page).min(last),
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            _ => position,
+        };
+        self.select_entry(&entries[position]);
+
+        Ok(())
+    }
+
+    // Selects the next row after the selected one starting with the given character, going back to
+    // the top of the list after its last row
+    // Records are matched by the artist they're sorted by, or their title when they're sorted by
+    // title, and group headers by their name
+    fn jump_to(&mut self, key: char) {
+        let entries = self.list_entries(&self.shown());
+        let position = self.cursor(&entries).unwrap_or(0);
+        let starts_with_key = |entry: &&ListEntry| {
+            let name = match entry {
+                ListEntry::Group { name, .. } => sort_name(name),
+                ListEntry::Record(i) => {
+                    let record = &self.collection[&self.sorted_ids[*i]];
+                    match self.settings.sort.key {
+                        SortKey::Title => record.title.to_lowercase(),
+                        _ => sort_name(record.artist()),
+                    }
+                }
+            };
+            name.chars()
+                .next()
+                .is_some_and(|c| c.eq_ignore_ascii_case(&key))
+        };
+
+        if let Some(entry) = (1..=entries.len())
+            .map(|step| &entries[(position + step) % entries.len()])
+            .find(starts_with_key)
+        {
+            self.select_entry(entry);
+        }
+    }
+
+    // Handles a key pressed while the filter's query is being edited
+    // The list narrows as the query is typed, and can still be moved through with the arrow and
+    // page keys. Enter stops editing but keeps the list filtered so that the selected match can be
+    // used with the app's other keys, while Esc, or erasing past the start of the query, closes
+    // the filter. The selected record stays selected once the whole list is shown again
+    fn edit_filter(&mut self, code: KeyCode) -> Result<()> {
+        let query_empty = self
+            .filter
+            .as_ref()
+            .is_none_or(|filter| filter.query.is_empty());
+
+        match code {
+            KeyCode::Esc => self.filter = None,
+            KeyCode::Backspace if query_empty => self.filter = None,
+            // There's nothing to keep the list filtered to when nothing matches
+            KeyCode::Enter if self.shown().is_empty() => self.filter = None,
+            KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Enter => {
+                if let Some(filter) = &mut self.filter {
+                    match code {
+                        KeyCode::Char(c) => filter.query.push(c),
+                        KeyCode::Backspace => {
+                            filter.query.pop();
+                        }
+                        _ => filter.editing = false,
+                    }
+                }
+            }
+            code => self.move_selection(code)?,
         }
+
+        Ok(())
     }
 
     // Handles command mode
     fn command_mode(&mut self) -> Result<()> {
-        execute!(stdout(), cursor::MoveTo(11, 37), cursor::Show)?;
-        // Disables raw mode so that the use can freely enter a command
-        terminal::disable_raw_mode()?;
+        // The cover image would be drawn over the command's pickers and messages
+        hide_cover(self.graphics())?;
+
+        // Read command from user, typed after "Command: " in the footer
+        // Cancelling the command with Esc goes back to the records list
+        let footer = Layout::current()?.footer;
+        let command = self.editor.read_line(
+            (footer.x + 11, footer.y + 1),
+            footer.width.saturating_sub(13),
+            command_completions,
+        )?;
+        execute!(stdout(), cursor::Hide)?;
+        let command = match &command {
+            Some(command) => command.as_str(),
+            None => return Ok(()),
+        };
 
-        // Read command from user
-        let mut command = String::new();
-        std::io::stdin().read_line(&mut command)?;
-        let command = command.trim_end();
+        // Splits the command's name from its argument, e.g. "Add 1234" -> ("Add", "1234")
+        let (name, argument) = match command.split_once(' ') {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
 
-        match command {
-            "Login" => self.login()?,
-            "Add" => self.add_record()?,
-            "Remove" => self.remove_selected()?,
-            _ => (),
+        let result = match name {
+            "Login" => self.login(),
+            "Add" if argument.is_empty() => self.add_record(),
+            "Add" | "AddRelease" => self.add_release(argument),
+            "Import" => self.import_collection(),
+            "Sync" => self.sync_collection(),
+            "Set" => self.set(argument),
+            "Remove" => self.remove_selected(),
+            "Restore" => self.restore(),
+            "Regenerate" => self.regenerate_covers(),
+            "Adjust" => self.adjust_selected(argument),
+            "Export" => self.export_selected(argument),
+            "" => Ok(()),
+            // Commands are case sensitive, so suggestions include the command with its casing
+            // corrected
+            _ => {
+                let suggestions = suggestions(name, &COMMANDS);
+                if suggestions.is_empty() {
+                    print_message(&format!(
+                        "'{}' is not a command. Commands: {}",
+                        name,
+                        COMMANDS.join(", ")
+                    ))
+                } else {
+                    print_message(&format!(
+                        "'{}' is not a command. Did you mean {}?",
+                        name,
+                        suggestions.join(" or ")
+                    ))
+                }
+            }
+        };
+
+        // Errors from a command, such as a failed request, are shown instead of quitting the app
+        if let Err(error) = result {
+            print_message(&format!("Error: {}", error))?;
         }
 
-        // Enable raw mode and resume regular print loop 
-        terminal::enable_raw_mode()?;
+        // Saves any changes the command made, even if it failed partway through
+        self.save()?;
+
+        // Resume regular print loop
         execute!(stdout(), cursor::Hide)?;
 
         Ok(())
@@ -275,187 +715,891 @@ impl App {
 
     // Handles user login
     fn login(&mut self) -> Result<()> {
-        // Prints prompt box
-        execute!(stdout(), cursor::MoveTo(0, 32))?;
-        print!("╚══════════════════════════════════════╝ ╚═══════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-        print!("╔════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╗\r\n");
-        print_blank_lines(4);
-        print!("╚════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-        execute!(stdout(), cursor::MoveTo(0, 34))?;
-
-        // Retrieves user authentication tokens needed to make authenticated requests
-        let user_data = authenticate(&self.client).unwrap();
+        // Retrieves user authentication tokens needed to make authenticated requests, after the
+        // user authorizes the application in their browser
+        let user_data = authenticate(&self.client, |url| {
+            let cols = Layout::current().map_or(MIN_COLS, |layout| layout.cols);
+            // The link is split over several lines rather than truncated when the terminal is too
+            // narrow for it, so that it can still be copied
+            let url: Vec<char> = url.chars().collect();
+            let url_lines: Vec<String> = url
+                .chunks(cols.saturating_sub(4).max(1) as usize)
+                .map(|line| line.iter().collect())
+                .collect();
+
+            let mut lines = vec!["Please authorize the application at the link below."];
+            lines.extend(url_lines.iter().map(|line| line.as_str()));
+            lines.push("Then paste the code here:");
+            let top = print_box(&lines)?;
+            read_line_at((28, top + lines.len() as u16 - 1))
+        })?;
         // Saves (and overwrites it) to a data file
         let data_string = serde_json::to_string(&user_data)?;
         std::fs::write("data/user_data.json", data_string)?;
         self.user_data = Some(user_data);
+        self.username = None;
 
-        print!("║ Login Successful!                                                                                                              ║\r\n");
-        execute!(stdout(), cursor::Hide)?;
-        wait_for_enter()?;
-
-        Ok(())
+        print_message("Login Successful!")
     }
 
     // Handles adding a new record to the collection
     fn add_record(&mut self) -> Result<()> {
-        match &self.user_data {
+        match self.user_data.clone() {
             // Authenticated requests are needed to retrieve image urls and search the database
             None => {
-                // Prints prompt box
-                execute!(stdout(), cursor::MoveTo(0, 36))?;
-                print!("╚══════════════════════════════════════╝ ╚═══════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-                print!("╔════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╗\r\n");
-                print!("║ You need to log into a Discogs account with the 'Login' command before adding a record to your collection.                     ║\r\n");
-                print!("╚════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-                wait_for_enter()?;
-
+                print_message("You need to log into a Discogs account with the 'Login' command before adding a record to your collection.")?;
             },
             Some(user_data) => {
                 // Prints prompt box
-                execute!(stdout(), cursor::MoveTo(0, 31))?;
-                print!("╚══════════════════════════════════════╝ ╚═══════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-                print!("╔════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╗\r\n");
-                print!("║ Enter the details of the record you want to add to your collection.                                                            ║\r\n");
-                print_blank_lines(1);
-                print!("║ Artist:                                                                                                                        ║\r\n");
-                print!("║ Album:                                                                                                                         ║\r\n");
-                print_blank_lines(1);
-                print!("╚════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-                execute!(stdout(), cursor::MoveTo(10, 35))?;
-
-                terminal::disable_raw_mode().unwrap();
+                let top = print_box(&[
+                    "Enter the details of the record you want to add to your collection.",
+                    "",
+                    "Artist:",
+                    "Album:",
+                    "",
+                ])?;
 
                 // Retrieves user input
-                let mut artist = String::new();
-                std::io::stdin().read_line(&mut artist).unwrap();
+                let artist = read_line_at((10, top + 2))?;
+                let album = read_line_at((9, top + 3))?;
 
-                execute!(std::io::stdout(), cursor::MoveTo(9, 36)).unwrap();
-                let mut album = String::new();
-                std::io::stdin().read_line(&mut album).unwrap();
+                // Searches the database for masters and releases matching the user's input
+                let query = format!("{} {}", artist.trim(), album.trim());
+                let results = search(&self.client, &user_data, &query)?;
 
-                terminal::enable_raw_mode().unwrap();
+                // Lets the user pick the exact release to add among the search results
+                let release_url = match pick_release(&self.client, &user_data, &results)? {
+                    Some(url) => url,
+                    None => {
+                        print_message("No record was added to your collection.")?;
+                        return Ok(());
+                    }
+                };
 
-                // Forms database url given the user information, limitting the search to master releases
-                let search_url = format!(
-                    "https://api.discogs.com/database/search?q={}-{}&type=master",
-                    process_search_string(artist),
-                    process_search_string(album)
-                );
+                // Creates a Record struct from the chosen release's information
+                let release = get_release(&self.client, &user_data, &release_url)?;
+                let new_record = Record::from_discogs(release, &self.settings.adjustments)?;
+                self.add_new_record(&user_data, new_record)?;
 
-                // Gets the results of searching
-                let search_data = make_auth_request(&self.client, user_data, search_url).unwrap();
-                let search: serde_json::Value = serde_json::from_str(&search_data.as_str())?;
+                print_message("Record added to your collection!")?;
+            }
+        }
 
-                // Gets the information from the master release (doesn't contain tracklist, country, etc.)
-                let master_data = make_auth_request(&self.client, user_data, search["results"][0]["master_url"].as_str().unwrap().into())
-                    .unwrap();
-                let master: serde_json::Value = serde_json::from_str(&master_data)?;
+        Ok(())
+    }
 
-                // Gets the information from the main release 
-                let release_data = make_auth_request(&self.client, user_data, master["main_release_url"].as_str().unwrap().into())
-                    .unwrap();
-                let main_release: serde_json::Value = serde_json::from_str(&release_data)?;
+    // Handles adding a record to the collection from its Discogs release ID or url
+    // Prompts for the ID if none was given with the command
+    fn add_release(&mut self, argument: &str) -> Result<()> {
+        let user_data = match &self.user_data {
+            Some(user_data) => user_data.clone(),
+            None => {
+                return print_message("You need to log into a Discogs account with the 'Login' command before adding a record to your collection.");
+            }
+        };
 
-                // Creates a Record struct from the main release's information
-                let new_record = Record::from_discogs(main_release)?;
-                let key = (new_record.artists[0].clone(), new_record.title.clone());
+        let mut input = argument.to_string();
+        if input.is_empty() {
+            // Prints prompt box
+            let top = print_box(&[
+                "Enter the Discogs release ID or url of the record you want to add to your collection.",
+                "",
+                "ID/URL:",
+            ])?;
+            input = read_line_at((10, top + 2))?;
+        }
 
-                // Shifts the selected index to not be affected by the new addition
-                if self.sorted_titles.len() != 0 && self.sorted_titles[self.selected] > key {
-                    self.selected += 1;
+        // Masters don't point to a specific pressing so the user picks one of its versions
+        let release_url = match DiscogsRef::parse(&input) {
+            Some(release @ DiscogsRef::Release(_)) => release.api_url(),
+            Some(master @ DiscogsRef::Master(_)) => {
+                let title = format!("Versions of {}", input.trim());
+                match pick_version(&self.client, &user_data, &master.api_url(), &title)? {
+                    Some(url) => url,
+                    None => return print_message("No record was added to your collection."),
                 }
+            }
+            None => {
+                return print_message(&format!(
+                    "'{}' is not a Discogs release ID or url.",
+                    input.trim()
+                ));
+            }
+        };
 
-                // Adds record to the collection...
-                match self.collection.insert(key.clone(), new_record) {
-                    Some(_) => (),
-                    None => {
-                        // ... and to the sorted_titles Vec if it's new
-                        self.sorted_titles.push(key);
-                        self.sorted_titles.sort();
+        let release = get_release(&self.client, &user_data, &release_url)?;
+        let new_record = Record::from_discogs(release, &self.settings.adjustments)?;
+        self.add_new_record(&user_data, new_record)?;
+
+        print_message("Record added to your collection!")
+    }
+
+    // Handles importing the records in the user's Discogs collection into the local collection
+    // Records that are already in the collection are linked to their Discogs copy instead
+    fn import_collection(&mut self) -> Result<()> {
+        let user_data = match &self.user_data {
+            Some(user_data) => user_data.clone(),
+            None => {
+                return print_message("You need to log into a Discogs account with the 'Login' command before importing your collection.");
+            }
+        };
+
+        let loading = "Retrieving your Discogs collection...";
+        print_progress(loading, 0, 1)?;
+        let username = self.username(&user_data)?;
+        let items = get_collection(&self.client, &user_data, &username, |page, pages| {
+            print_progress(loading, page, pages)
+        })?;
+
+        // Records that are already in the collection are linked to their Discogs copy, and only
+        // the releases that aren't in the collection yet need to be fetched
+        let (record_matches, matched) = self.match_discogs_items(&items);
+        for (id, found) in record_matches {
+            if let (Some(i), Some(record)) = (found, self.collection.get_mut(&id)) {
+                record.release_id = Some(items[i].release_id);
+                record.instance = Some(items[i].instance);
+                self.modified = true;
+            }
+        }
+        let new_items: Vec<&CollectionItem> = items
+            .iter()
+            .zip(matched.iter())
+            .filter(|(_, &matched)| !matched)
+            .map(|(item, _)| item)
+            .collect();
+        let skipped = items.len() - new_items.len();
+
+        let (mut imported, mut failed) = (0, 0);
+        for (i, item) in new_items.iter().enumerate() {
+            if cancel_requested()? {
+                break;
+            }
+
+            let label = format!("Importing {} - {}   (Esc: Cancel)", item.artist, item.title);
+            print_progress(&label, i as u64, new_items.len() as u64)?;
+
+            // A release that fails to load doesn't stop the rest of the import
+            match get_release(&self.client, &user_data, &item.resource_url)
+                .and_then(|release| Record::from_discogs(release, &self.settings.adjustments))
+            {
+                Ok(mut record) => {
+                    record.instance = Some(item.instance);
+                    self.insert_record(record);
+                    imported += 1;
+
+                    // Saves regularly so that a long import isn't lost if the app crashes
+                    if imported % IMPORT_SAVE_INTERVAL == 0 {
+                        self.save()?;
                     }
                 }
+                Err(_) => failed += 1,
+            }
+        }
+
+        print_message(&format!(
+            "Imported {} records from your Discogs collection. {} were already in your collection, {} failed to load.",
+            imported, skipped, failed
+        ))
+    }
 
-                print!("║ Record added to your collection!                                                                                              ║\r\n");
+    // Handles reconciling the collection with the user's Discogs collection
+    // Records missing from either side are added to it, and records found on both sides are linked
+    // to their Discogs copy. Records removed from one side since they were linked are removed
+    // from the other: a record whose linked Discogs copy is gone is removed from the collection,
+    // and a Discogs copy listed in removed_instances, which holds the copies of records removed
+    // here without removing them from Discogs, is removed from Discogs. The changes are listed for
+    // the user to confirm before they are applied
+    fn sync_collection(&mut self) -> Result<()> {
+        let user_data = match &self.user_data {
+            Some(user_data) => user_data.clone(),
+            None => {
+                return print_message("You need to log into a Discogs account with the 'Login' command before syncing your collection.");
+            }
+        };
 
-                execute!(stdout(), cursor::Hide)?;
-                wait_for_enter()?;
+        let loading = "Retrieving your Discogs collection...";
+        print_progress(loading, 0, 1)?;
+        let username = self.username(&user_data)?;
+        let items = get_collection(&self.client, &user_data, &username, |page, pages| {
+            print_progress(loading, page, pages)
+        })?;
+
+        let (record_matches, matched) = self.match_discogs_items(&items);
+        let mut changes = Vec::new();
+        let mut unsyncable = 0;
+        for (id, found) in record_matches {
+            let record = &self.collection[&id];
+            match found {
+                Some(i) if record.instance != Some(items[i].instance) => {
+                    changes.push(SyncChange::Link(id, i))
+                }
+                Some(_) => (),
+                // A record linked to a Discogs copy that's gone was removed from Discogs
+                None if record.instance.is_some() => changes.push(SyncChange::RemoveRecord(id)),
+                None if record.release_id.is_some() => changes.push(SyncChange::Upload(id)),
+                None => unsyncable += 1,
             }
         }
+        for (i, _) in matched.iter().enumerate().filter(|(_, &m)| !m) {
+            if self.removed_instances.contains(&items[i].instance) {
+                changes.push(SyncChange::RemoveItem(i));
+            } else {
+                changes.push(SyncChange::Download(i));
+            }
+        }
+
+        // Forgets the removed records whose Discogs copies are gone or back in the collection
+        let removed_count = self.removed_instances.len();
+        self.removed_instances.retain(|instance| {
+            changes.iter().any(|change| {
+                matches!(change, SyncChange::RemoveItem(i) if items[*i].instance == *instance)
+            })
+        });
+        if self.removed_instances.len() != removed_count {
+            save_removed_instances(&self.removed_instances)?;
+        }
+
+        if changes.is_empty() {
+            return print_message(
+                "Your collection is already in sync with your Discogs collection.",
+            );
+        }
+
+        // Lists the changes for the user to confirm
+        let rows = changes
+            .iter()
+            .map(|change| match change {
+                SyncChange::Upload(id) => vec![
+                    "Add to Discogs".to_string(),
+                    self.collection[id].display_title(),
+                ],
+                SyncChange::Download(i) => vec![
+                    "Add to collection".to_string(),
+                    format!("{} - {}", items[*i].artist, items[*i].title),
+                ],
+                SyncChange::Link(id, _) => vec![
+                    "Link to Discogs copy".to_string(),
+                    self.collection[id].display_title(),
+                ],
+                SyncChange::RemoveRecord(id) => vec![
+                    "Remove from collection".to_string(),
+                    self.collection[id].display_title(),
+                ],
+                SyncChange::RemoveItem(i) => vec![
+                    "Remove from Discogs".to_string(),
+                    format!("{} - {}", items[*i].artist, items[*i].title),
+                ],
+            })
+            .collect();
+        let mut title = format!("Sync with Discogs: {} changes", changes.len());
+        if unsyncable > 0 {
+            title.push_str(&format!(
+                " ({} records without a Discogs release ID can't be synced)",
+                unsyncable
+            ));
+        }
+        let mut picker = Picker::new(&title, vec![("Change", 24), ("Record", 101)], rows)
+            .with_hint("Enter: Apply changes   Esc: Cancel");
+        if picker.run()?.is_none() {
+            return print_message("Cancelled sync, no changes were made.");
+        }
+
+        // Applies the changes, counting the ones that fail rather than stopping
+        let mut failed = 0;
+        for (i, change) in changes.iter().enumerate() {
+            print_progress(
+                "Syncing with your Discogs collection...",
+                i as u64,
+                changes.len() as u64,
+            )?;
+
+            let result = match change {
+                SyncChange::Upload(id) => {
+                    let release_id = self.collection[id].release_id.unwrap_or_default();
+                    add_to_collection(&self.client, &user_data, &username, release_id).map(
+                        |instance| {
+                            if let Some(record) = self.collection.get_mut(id) {
+                                record.instance = Some(instance);
+                                self.modified = true;
+                            }
+                        },
+                    )
+                }
+                SyncChange::Download(i) => {
+                    get_release(&self.client, &user_data, &items[*i].resource_url)
+                        .and_then(|release| {
+                            Record::from_discogs(release, &self.settings.adjustments)
+                        })
+                        .map(|mut record| {
+                            record.instance = Some(items[*i].instance);
+                            self.insert_record(record);
+                        })
+                }
+                SyncChange::Link(id, i) => {
+                    if let Some(record) = self.collection.get_mut(id) {
+                        record.release_id = Some(items[*i].release_id);
+                        record.instance = Some(items[*i].instance);
+                        self.modified = true;
+                    }
+                    Ok(())
+                }
+                SyncChange::RemoveRecord(id) => {
+                    self.remove_record(id);
+                    Ok(())
+                }
+                SyncChange::RemoveItem(i) => {
+                    let item = &items[*i];
+                    remove_from_collection(
+                        &self.client,
+                        &user_data,
+                        &username,
+                        item.release_id,
+                        item.instance,
+                    )
+                    .map(|_| {
+                        self.removed_instances
+                            .retain(|instance| *instance != item.instance)
+                    })
+                }
+            };
+            if result.is_err() {
+                failed += 1;
+            }
+        }
+        save_removed_instances(&self.removed_instances)?;
+
+        print_message(&format!(
+            "Synced with your Discogs collection: {} changes applied, {} failed.",
+            changes.len() - failed,
+            failed
+        ))
+    }
+
+    // Handles changing a setting, e.g. "Set sync on"
+    fn set(&mut self, argument: &str) -> Result<()> {
+        let (key, value) = match argument.split_once(' ') {
+            Some((key, value)) => (key, value.trim()),
+            None => (argument, ""),
+        };
+
+        let changed = match key {
+            "sync" => parse_switch(value).map(|on| self.settings.discogs_sync = on),
+            "render" => parse_auto(value, RenderMode::parse).map(|mode| {
+                self.settings.render_mode = mode;
+                // The cover art is rendered again in the new mode
+                self.cover_art = None;
+            }),
+            "color" => parse_auto(value, ColorSupport::parse).map(|colors| {
+                self.settings.color_support = colors;
+                self.cover_art = None;
+            }),
+            "graphics" => parse_auto(value, GraphicsProtocol::parse).map(|graphics| {
+                self.settings.graphics = graphics;
+                self.cover_art = None;
+            }),
+            // The records are listed again in the new order, keeping the same record selected
+            "sort" => SortOrder::parse(value).map(|order| {
+                self.settings.sort = order;
+                self.sort_list();
+            }),
+            "group" => match value.to_lowercase().as_str() {
+                "off" | "none" => Some(None),
+                _ => Grouping::parse(value).map(Some),
+            }
+            .map(|grouping| {
+                self.settings.grouping = grouping;
+                self.sort_list();
+            }),
+            _ if ADJUSTMENTS.contains(&key) => {
+                self.cover_art = None;
+                self.settings.adjustments.set(key, value)
+            }
+            _ => None,
+        };
+        if changed.is_none() {
+            return print_message(&format!(
+                "'{}' is not a valid setting. Settings: {}, {}",
+                argument,
+                settings_help(),
+                ADJUSTMENTS_HELP
+            ));
+        }
+        self.settings.save()?;
+
+        print_message("Setting saved.")
+    }
+
+    // Handles changing the adjustments made to the selected record's cover, e.g.
+    // "Adjust contrast 1.2", which start from the ones in the settings. "Adjust reset" goes back to
+    // the ones in the settings
+    fn adjust_selected(&mut self, argument: &str) -> Result<()> {
+        let record = match self.selected_id() {
+            Some(id) => self.collection.get_mut(&id),
+            None => None,
+        };
+        let record = match record {
+            Some(record) => record,
+            None => return print_message("There is no record selected to adjust."),
+        };
+
+        let (key, value) = match argument.split_once(' ') {
+            Some((key, value)) => (key, value.trim()),
+            None => (argument, ""),
+        };
+        if key == "reset" {
+            record.adjustments = None;
+        } else {
+            let mut adjustments = record.adjustments(&self.settings.adjustments);
+            if adjustments.set(key, value).is_none() {
+                return print_message(&format!(
+                    "'{}' is not a valid adjustment. Adjustments: {}, reset",
+                    argument, ADJUSTMENTS_HELP
+                ));
+            }
+            record.adjustments = Some(adjustments);
+        }
+        self.modified = true;
+        self.cover_art = None;
+
+        print_message("Adjustment saved. Run Regenerate to also update the saved cover art.")
+    }
+
+    // Handles exporting the selected record's card, e.g. "Export html" or "Export png cover.png"
+    // The format can also be left out when the path's extension names it, e.g. "Export card.ans"
+    // The cover art is rendered at the size it's saved at, with the current render mode and
+    // adjustments
+    fn export_selected(&self, argument: &str) -> Result<()> {
+        let record = match self.selected_id() {
+            Some(id) => &self.collection[&id],
+            None => return print_message("There is no record selected to export."),
+        };
+
+        let (first, rest) = match argument.split_once(' ') {
+            Some((first, rest)) => (first, Some(rest.trim())),
+            None => (argument, None),
+        };
+        let (format, path) = match ExportFormat::parse(first) {
+            Some(format) => (Some(format), rest),
+            None if !argument.is_empty() => (ExportFormat::from_path(argument), Some(argument)),
+            None => (None, None),
+        };
+        let format = match format {
+            Some(format) => format,
+            None => {
+                return print_message(&format!(
+                    "'{}' is not a valid export. Export ansi/html/png [path], or a path ending in \
+                    .ans, .html or .png",
+                    argument
+                ))
+            }
+        };
+
+        // Records without a cached cover are exported with the art saved in the collection
+        let rendered = match record.load_cover() {
+            Some(cover) => Some(AsciiArt::from_image_with_mode(
+                cover,
+                COVER_ART_SIZE.0,
+                COVER_ART_SIZE.1,
+                self.render_mode(),
+                &record.adjustments(&self.settings.adjustments),
+            )?),
+            None => None,
+        };
+        let art = rendered.as_ref().unwrap_or(&record.image);
+
+        let path = export_card(record, art, format, self.colors(), path)?;
+        print_message(&format!("Exported {} to {}.", record.title, path.display()))
+    }
+
+    // Adds a newly fetched record to the collection, and to the user's Discogs collection when
+    // sync is turned on
+    fn add_new_record(&mut self, user_data: &UserData, mut record: Record) -> Result<()> {
+        if let (true, Some(release_id)) = (self.settings.discogs_sync, record.release_id) {
+            let username = self.username(user_data)?;
+            let instance = add_to_collection(&self.client, user_data, &username, release_id)?;
+            record.instance = Some(instance);
+        }
+        self.insert_record(record);
+
+        Ok(())
+    }
+
+    // Returns the logged in user's Discogs username, retrieving it the first time it's needed
+    fn username(&mut self, user_data: &UserData) -> Result<String> {
+        match &self.username {
+            Some(username) => Ok(username.clone()),
+            None => {
+                let username = get_username(&self.client, user_data)?;
+                self.username = Some(username.clone());
+                Ok(username)
+            }
+        }
+    }
+
+    // Handles rolling the collection back to one of its backups
+    fn restore(&mut self) -> Result<()> {
+        let backups = list_backups()?;
+        if backups.is_empty() {
+            return print_message("There are no backups of your collection yet.");
+        }
+
+        let rows = backups
+            .iter()
+            .map(|backup| {
+                vec![
+                    format!("{} UTC", format_timestamp(backup.timestamp)),
+                    backup
+                        .record_count()
+                        .map_or("?".to_string(), |count| count.to_string()),
+                ]
+            })
+            .collect();
+        let mut picker = Picker::new(
+            "Restore a Backup",
+            vec![("Saved at", 24), ("Records", 101)],
+            rows,
+        )
+        .with_hint("Enter: Restore   Esc: Cancel");
+        let backup = match picker.run()? {
+            Some(i) => &backups[i],
+            None => return print_message("Cancelled restoring a backup."),
+        };
+
+        self.collection = restore_backup(backup)?;
+        self.sorted_ids = self.collection.keys().copied().collect();
+        self.sort_list();
+        self.selected = 0;
+        self.on_header = false;
+        self.modified = true;
+
+        print_message(&format!(
+            "Restored your collection from the backup saved at {} UTC.",
+            format_timestamp(backup.timestamp)
+        ))
+    }
+
+    // Handles rendering the records' cover art again from their cached original covers
+    // Records added before covers were cached have their cover downloaded again from Discogs when
+    // the user is logged in, and records added before labels were saved get their labels
+    fn regenerate_covers(&mut self) -> Result<()> {
+        let ids = self.sorted_ids.clone();
+        let (mut regenerated, mut missing) = (0, 0);
+
+        for (i, id) in ids.iter().enumerate() {
+            if cancel_requested()? {
+                break;
+            }
+
+            let record = &self.collection[id];
+            let label = format!("Regenerating {}   (Esc: Cancel)", record.display_title());
+            print_progress(&label, i as u64, ids.len() as u64)?;
+
+            // Records whose cover isn't cached, or that were added before labels were saved, have
+            // their release retrieved again to fill in what they're missing
+            let mut cover = record.load_cover();
+            if cover.is_none() || record.label.is_empty() {
+                if let (Some(user_data), Some(release_id)) = (&self.user_data, record.release_id) {
+                    // A release that can't be retrieved is counted as missing, like in an import,
+                    // rather than stopping the other records from being regenerated
+                    let release_url = DiscogsRef::Release(release_id).api_url();
+                    let release = get_release(&self.client, user_data, &release_url).ok();
+                    if let (Some(release), Some(record)) = (release, self.collection.get_mut(id)) {
+                        if record.label.is_empty() {
+                            record.label = label_names(&release);
+                            self.modified = true;
+                        }
+                        if let (None, Some(url)) = (&cover, cover_url(&release)) {
+                            cover = record.fetch_cover(url);
+                        }
+                    }
+                }
+            }
+
+            match (cover, self.collection.get_mut(id)) {
+                (Some(cover), Some(record)) => {
+                    record.image = AsciiArt::from_image_with_mode(
+                        cover,
+                        COVER_ART_SIZE.0,
+                        COVER_ART_SIZE.1,
+                        RenderMode::Block,
+                        &record.adjustments(&self.settings.adjustments),
+                    )?;
+                    self.cover_art = None;
+                    regenerated += 1;
+                    self.modified = true;
+                }
+                _ => missing += 1,
+            }
+        }
+
+        print_message(&format!(
+            "Regenerated the cover art of {} records. {} records don't have a cover to render.",
+            regenerated, missing
+        ))
+    }
+
+    // Prepares the selected record's cover from its cached original when the selection changes,
+    // as an image if the terminal can draw one and as text art otherwise
+    fn update_cover_art(&mut self) -> Result<()> {
+        let record = match self.selected_id() {
+            Some(id) => &self.collection[&id],
+            None => return Ok(()),
+        };
+        if matches!(&self.cover_art, Some((id, _)) if *id == record.id) {
+            return Ok(());
+        }
+
+        // The cover is prepared at the size of its pane, unless the terminal is too small for it
+        let cells = match Layout::current()?.cover {
+            Some(cover) => (cover.width, cover.height),
+            None => {
+                self.cover_art = None;
+                return Ok(());
+            }
+        };
+
+        let cover = match record.load_cover() {
+            Some(cover) => cover,
+            None => {
+                self.cover_art = None;
+                return Ok(());
+            }
+        };
+        let art = match CoverImage::new(self.graphics(), cover.clone(), cells)? {
+            Some(image) => CoverArt::Image(image),
+            None => CoverArt::Text(AsciiArt::from_image_with_mode(
+                cover,
+                cells.0 as usize,
+                cells.1 as usize,
+                self.render_mode(),
+                &record.adjustments(&self.settings.adjustments),
+            )?),
+        };
+        self.cover_art = Some((record.id, art));
 
         Ok(())
     }
 
+    // The graphics protocol the cover is drawn with, as set in the settings or detected from the
+    // terminal
+    fn graphics(&self) -> GraphicsProtocol {
+        self.settings.graphics.unwrap_or(self.detected_graphics)
+    }
+
+    // The colors the cover art is printed in, as set in the settings or detected from the terminal
+    fn colors(&self) -> ColorSupport {
+        self.settings.color_support.unwrap_or(self.detected_colors)
+    }
+
+    // The render mode the cover art is drawn in, as set in the settings or the best one for the
+    // terminal's colors
+    fn render_mode(&self) -> RenderMode {
+        self.settings
+            .render_mode
+            .unwrap_or_else(|| self.colors().default_render_mode())
+    }
+
+    // Saves the collection if it has changed since it was last saved
+    fn save(&mut self) -> Result<()> {
+        if self.modified {
+            save_collection(&self.collection)?;
+            self.modified = false;
+        }
+        Ok(())
+    }
+
+    // Matches each record with a copy in the user's Discogs collection, first by its instance, then
+    // by its release ID, and by artist and title for records added before they had release IDs
+    // Returns the index of the item matched with each record, and whether each item was matched
+    fn match_discogs_items(
+        &self,
+        items: &[CollectionItem],
+    ) -> (Vec<(Uuid, Option<usize>)>, Vec<bool>) {
+        let mut matched = vec![false; items.len()];
+        let mut record_matches = Vec::new();
+
+        for id in self.sorted_ids.iter() {
+            let record = &self.collection[id];
+            let find = |matches: &dyn Fn(&CollectionItem) -> bool| {
+                (0..items.len()).find(|&i| !matched[i] && matches(&items[i]))
+            };
+            let found = find(&|item| Some(item.instance) == record.instance)
+                .or_else(|| find(&|item| Some(item.release_id) == record.release_id))
+                .or_else(|| match record.release_id {
+                    None => {
+                        find(&|item| item.artist == record.artist() && item.title == record.title)
+                    }
+                    Some(_) => None,
+                });
+
+            if let Some(i) = found {
+                matched[i] = true;
+            }
+            record_matches.push((*id, found));
+        }
+
+        (record_matches, matched)
+    }
+
+    // Adds a record to the collection, keeping the sorted_ids list and the selection in sync
+    fn insert_record(&mut self, record: Record) {
+        self.sorted_ids.push(record.id);
+        self.collection.insert(record.id, record);
+        self.sort_list();
+        self.modified = true;
+    }
+
+    // Removes a record from both the collection and the sorted_ids list, keeping the selection
+    // on the record that takes its place
+    fn remove_record(&mut self, id: &Uuid) {
+        // The record's cached cover is deleted along with it
+        if let Some(record) = self.collection.remove(id) {
+            record.delete_cover();
+        }
+        if let Some(index) = self.sorted_ids.iter().position(|sorted_id| sorted_id == id) {
+            self.sorted_ids.remove(index);
+            if index < self.selected {
+                self.selected -= 1;
+            }
+        }
+        self.modified = true;
+        self.selected = self.selected.min(self.sorted_ids.len().saturating_sub(1));
+    }
+
+    // Sorts the sorted_ids list in the order set in the settings, e.g. after a record is added or
+    // the order is changed
+    fn sort_list(&mut self) {
+        let selected_id = self.sorted_ids.get(self.selected).copied();
+
+        sort_records(
+            &self.collection,
+            &mut self.sorted_ids,
+            &self.settings.sort,
+            self.settings.grouping,
+        );
+
+        // Moves the selected index so that the same record stays selected
+        if let Some(selected_id) = selected_id {
+            self.selected = self
+                .sorted_ids
+                .iter()
+                .position(|id| *id == selected_id)
+                .unwrap_or(self.selected);
+        }
+    }
+
     // Handles removing the selected record from the collection
     fn remove_selected(&mut self) -> Result<()> {
+        if self.selected_id().is_none() {
+            return Ok(());
+        }
+
         // Prompt string
+        let record = &self.collection[&self.sorted_ids[self.selected]];
         let mut remove_str = format!(
             "Are you sure you want to delete {} by {} from your collection (y/n)? ",
-            self.sorted_titles[self.selected].1,
-            self.sorted_titles[self.selected].0
+            record.title,
+            record.artist()
         );
 
-        // Trims it to fit within the prompt boxes
-        max_len(&mut remove_str, 126);
-
-        execute!(stdout(), cursor::MoveTo(0, 35))?;
-        print!("╚══════════════════════════════════════╝ ╚═══════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-        print!("╔════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╗\r\n");
-
-        print!("║ {:126} ║\r\n", remove_str);
-        print!("╚════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════════╝\r\n");
-        execute!(stdout(), cursor::MoveTo(2 + remove_str.len() as u16, 37))?;
+        // Trims it to fit within the prompt box
+        let cols = Layout::current()?.cols;
+        max_len(&mut remove_str, cols.saturating_sub(4) as usize);
 
-        terminal::disable_raw_mode().unwrap();
+        // Retrieves user input, typed after the prompt
+        let top = print_box(&[&remove_str])?;
+        let response = read_line_at((2 + remove_str.chars().count() as u16, top))?;
 
-        // Retrieves user input
-        let mut response = String::new();
-        std::io::stdin().read_line(&mut response).unwrap();
-        let response = response.trim();
-
-        terminal::enable_raw_mode().unwrap();
-
-        match response {
+        match response.as_str() {
             "y" | "yes" | "Y" | "Yes" => {
-                // Removes record from both the collection and the sorted_titles list
-                self.collection.remove(&self.sorted_titles[self.selected]);
-                self.sorted_titles.remove(self.selected);
-                self.selected = self.selected.min(self.sorted_titles.len() - 1);
+                // Deletes the record's copy from the user's Discogs collection first when syncing,
+                // so that a failed request leaves the record in both collections
+                let id = self.sorted_ids[self.selected];
+                let record = &self.collection[&id];
+                match (
+                    self.settings.discogs_sync,
+                    self.user_data.clone(),
+                    record.release_id,
+                    record.instance,
+                ) {
+                    (true, Some(user_data), Some(release_id), Some(instance)) => {
+                        let username = self.username(&user_data)?;
+                        remove_from_collection(
+                            &self.client,
+                            &user_data,
+                            &username,
+                            release_id,
+                            instance,
+                        )?;
+                    }
+                    // Otherwise the Discogs copy is remembered so that Sync can remove it later
+                    (_, _, _, Some(instance)) => {
+                        self.removed_instances.push(instance);
+                        save_removed_instances(&self.removed_instances)?;
+                    }
+                    _ => (),
+                }
 
-                execute!(stdout(), cursor::MoveTo(0, 37))?;
-                print!("║ Record removed from collection!                                                                                        ║\r\n");
+                self.remove_record(&id);
+
+                print_message("Record removed from collection!")
             }
-            _ => print!("║ Cancelled removal of record.                                                                                           ║\r\n"),
+            _ => print_message("Cancelled removal of record."),
         }
-        execute!(stdout(), cursor::Hide)?;
-        wait_for_enter()?;
-
-        Ok(())
     }
 
     // Quits the application after running it 
     pub fn quit(self) -> Result<()> {
+        // Removes the cover image from the terminal
+        clear_cover(self.graphics())?;
+
         // Disables raw mode
         terminal::disable_raw_mode()?;
 
         // And writes collection data to a file so that it can be retrieved on startup
-        let records = self.collection.into_values().collect::<Vec<Record>>();
-        let collection_string = serde_json::to_string(&records)?;
-        std::fs::write("data/collection.json", collection_string)?;
+        save_collection(&self.collection)?;
 
         Ok(())
     }
 }
 
-// Loops until a resize occurs
-fn wait_for_resize() -> Result<()> {
-    loop {
-        match event::read()? {
-            event::Event::Resize(..) => break,
-            _ => (),
-        }
-    }
-    Ok(())
+// The selected record's cover as it is drawn
+// Image: Drawn by the terminal with a graphics protocol
+// Text: Drawn as AsciiArt
+enum CoverArt {
+    Image(CoverImage),
+    Text(AsciiArt),
+}
+
+// A row of the records list
+// Group: The header of a group of records when the list is grouped, with its name, the key its
+//        records are grouped by, the index of its first record in sorted_ids, the number of
+//        records in it and whether they're hidden
+// Record: A record, by its index in sorted_ids
+#[derive(Clone)]
+enum ListEntry {
+    Group {
+        name: String,
+        key: String,
+        first: usize,
+        count: usize,
+        collapsed: bool,
+    },
+    Record(usize),
+}
+
+// A change to apply when syncing with the user's Discogs collection
+// Upload: Adds the record with the given ID to the Discogs collection
+// Download: Adds the Discogs collection item with the given index to the collection
+// Link: Links the record with the given ID to the Discogs collection item with the given index
+// RemoveRecord: Removes the record with the given ID, whose Discogs copy was removed
+// RemoveItem: Removes the Discogs collection item with the given index, whose record was removed
+enum SyncChange {
+    Upload(Uuid),
+    Download(usize),
+    Link(Uuid, usize),
+    RemoveRecord(Uuid),
+    RemoveItem(usize),
 }
 
 // Loops until the Enter key is pressed
@@ -468,103 +1612,369 @@ fn wait_for_enter() -> Result<()> {
         };
 
     loop {
-        match read()? {
-            Key(KeyEvent { code, .. }) => match code {
-                    KeyCode::Enter => break,
-                    _ => (),
-            },
-            _ => (),
+        if let Key(KeyEvent {
+            code: KeyCode::Enter,
+            ..
+        }) = read()?
+        {
+            break;
         }
     }
     Ok(())
 
 }
 
-// Prints n blanks section rows with boundaries corresponding to APP_COLS
-fn print_blank_lines(n: u32) {
-    for _ in 0..n {
-        print!("║                                                                                                                                ║\r\n");
+// The words that can follow the given words of a command, which Tab completes in command mode,
+// e.g. the settings after "Set" and their values after "Set render"
+fn command_completions(words: &[&str]) -> Vec<String> {
+    let completions: Vec<&str> = match words {
+        [] => COMMANDS.to_vec(),
+        ["Set"] => SETTINGS.iter().chain(ADJUSTMENTS.iter()).copied().collect(),
+        ["Set", setting @ ..] => setting_values(setting),
+        ["Adjust", "linear" | "levels" | "tint"] => value_names(&SWITCH_NAMES),
+        ["Adjust"] => ADJUSTMENTS
+            .iter()
+            .chain(["reset"].iter())
+            .copied()
+            .collect(),
+        ["Export"] => value_names(&ExportFormat::NAMES),
+        _ => Vec::new(),
+    };
+
+    completions
+        .into_iter()
+        .map(|word| word.to_string())
+        .collect()
+}
+
+// The values a setting can be set to, from the words written after the Set command, e.g. the
+// render modes after "render" and the directions after "sort year"
+fn setting_values(words: &[&str]) -> Vec<&'static str> {
+    let auto = |names: Vec<&'static str>| [vec!["auto"], names].concat();
+    match words {
+        ["sync" | "linear" | "levels" | "tint"] => value_names(&SWITCH_NAMES),
+        ["render"] => auto(value_names(&RenderMode::NAMES)),
+        ["color"] => auto(value_names(&ColorSupport::NAMES)),
+        ["graphics"] => auto(value_names(&GraphicsProtocol::NAMES)),
+        ["sort"] => value_names(&SortKey::NAMES),
+        ["sort", _] => value_names(&SortOrder::DIRECTIONS),
+        ["group"] => [vec!["off"], value_names(&Grouping::NAMES)].concat(),
+        _ => Vec::new(),
     }
 }
 
-// Takes user's artist and title input and returns a string that can be appended to the search url
-// For example: "stan getz / joao gilberto" -> "Stan+Getz+Joao+Gilberto"
-fn process_search_string(s: String) -> String {
-    let v: Vec<&str> = s.trim().split(' ').collect();
-    let mut w: Vec<String> = Vec::new();
-    for s in v.iter() {
-        // Removes non alphanumeric characters e.g. " / " or " - "
-        if s.len() == 1 && !s.to_string().chars().nth(0).unwrap().is_alphanumeric() {
-            continue;
-        } else {
-            // Capitalizes the first letter of each word
-            let mut c = s.chars();
-            match c.next() {
-                None => w.push(String::new()),
-                Some(f) => {
-                    let capitalized = f.to_uppercase().collect::<String>() + c.as_str();
-                    w.push(capitalized);
-                }
+// Lists the settings other than the adjustments with their values, e.g.
+// "sort artist/title/... asc/desc", for when a setting isn't valid
+fn settings_help() -> String {
+    let settings: Vec<String> = SETTINGS
+        .iter()
+        .map(|setting| {
+            let mut help = format!("{} {}", setting, setting_values(&[setting]).join("/"));
+            // Settings with a second word, like a sort order's direction, list its values too
+            let second = setting_values(&[setting, ""]);
+            if !second.is_empty() {
+                help.push_str(&format!(" {}", second.join("/")));
             }
-        }
+            help
+        })
+        .collect();
+
+    settings.join(", ")
+}
+
+// The number of records shown in the records list, between its title and bottom border
+fn list_rows(layout: &Layout) -> usize {
+    layout.list.height.saturating_sub(4).max(1) as usize
+}
+
+// Prints text at a position of the terminal
+fn print_at(position: (u16, u16), text: &str) -> Result<()> {
+    queue!(
+        stdout(),
+        cursor::MoveTo(position.0, position.1),
+        Print(text)
+    )
+}
+
+// Prints a box over the bottom of the screen holding the given lines, closing off the panes above
+// it, and returns the row of the box's first line so that input can be read within it
+fn print_box(lines: &[&str]) -> Result<u16> {
+    let layout = Layout::current()?;
+    let top = layout.rows.saturating_sub(lines.len() as u16 + 2);
+
+    print_at((0, top.saturating_sub(1)), &layout.panes_bottom())?;
+    print_at((0, top), &border(layout.cols, TOP))?;
+    for (i, line) in lines.iter().enumerate() {
+        print_at((0, top + 1 + i as u16), &boxed(line, layout.cols))?;
     }
+    print_at(
+        (0, top + 1 + lines.len() as u16),
+        &border(layout.cols, BOTTOM),
+    )?;
+    stdout().flush()?;
 
-    // Joins the processed words with a '+'
-    w.join("+")
+    Ok(top + 1)
 }
 
-// Truncates a given string to len, appending "..." at the end
-fn max_len(string: &mut String, len: usize) -> &mut String {
-    if string.len() > len {
-        string.truncate(len - 3);
+// Reads a line the user types at a position, such as within a prompt box
+// Raw mode is disabled while the user types so that the terminal shows and edits the line
+fn read_line_at(position: (u16, u16)) -> Result<String> {
+    execute!(
+        stdout(),
+        cursor::MoveTo(position.0, position.1),
+        cursor::Show
+    )?;
+    terminal::disable_raw_mode()?;
+
+    let mut line = String::new();
+    let result = std::io::stdin().read_line(&mut line);
+
+    terminal::enable_raw_mode()?;
+    execute!(stdout(), cursor::Hide)?;
+    result?;
+
+    Ok(line.trim().to_string())
+}
+
+// Asks the user to enlarge a terminal that's too small to draw the app in
+fn print_too_small(layout: &Layout) -> Result<()> {
+    let mut message = format!(
+        "Vinylla needs a terminal of at least {}x{}. Resize it to continue, or press q to quit.",
+        MIN_COLS, MIN_ROWS
+    );
+    max_len(&mut message, layout.cols as usize);
+
+    execute!(
+        stdout(),
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, layout.rows / 2),
+        Print(message)
+    )
+}
+
+// Truncates a given string to len characters, appending "..." at the end
+pub(crate) fn max_len(string: &mut String, len: usize) -> &mut String {
+    if string.chars().count() > len {
+        // Finds the byte index of the cut so that multi-byte characters aren't split
+        let end = string
+            .char_indices()
+            .nth(len.saturating_sub(3))
+            .map_or(string.len(), |(i, _)| i);
+        string.truncate(end);
         string.push_str("...");
     }
 
     string
 }
 
-// Prints a given record's tracklist at a maximum of 15 rows
-// start_row is not really necessary as it is always printed starting from the same row
-fn print_tracklist(start_row: u16, record: &Record) -> Result<()> {
+// Prints a message in a prompt box over the footer and waits for the user to press Enter
+fn print_message(message: &str) -> Result<()> {
+    print_box(&[message])?;
+    execute!(stdout(), cursor::Hide)?;
+    wait_for_enter()
+}
+
+// Prints a progress bar in a box over the footer, labelled with the task being done
+fn print_progress(label: &str, done: u64, total: u64) -> Result<()> {
+    // The bar fills the box's width, leaving space for the percentage and brackets
+    let bar_width = Layout::current()?.cols.saturating_sub(4 + 7) as u64;
+    let filled = (done.min(total) * bar_width / total.max(1)) as usize;
+    let bar = format!(
+        "{:>3}% [{}{}]",
+        done.min(total) * 100 / total.max(1),
+        "█".repeat(filled),
+        "░".repeat(bar_width as usize - filled)
+    );
+
+    print_box(&[label, &bar])?;
+    execute!(stdout(), cursor::Hide)?;
+
+    Ok(())
+}
+
+// Checks, without blocking, whether the user pressed Esc to cancel a long running task
+fn cancel_requested() -> Result<bool> {
+    use crossterm::event::{poll, read, Event::Key, KeyCode, KeyEvent};
+
+    while poll(std::time::Duration::from_millis(0))? {
+        if let Key(KeyEvent {
+            code: KeyCode::Esc, ..
+        }) = read()?
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// The columns shown by the picker when choosing among search results and master versions
+const RESULT_COLUMNS: [(&str, usize); 7] = [
+    ("Title", 36),
+    ("Type", 7),
+    ("Year", 6),
+    ("Country", 14),
+    ("Format", 24),
+    ("Label", 20),
+    ("Cat#", 13),
+];
+
+// Lets the user pick a release among search results, returning the chosen release's url
+// Picking a master opens a second picker with its versions so that the exact pressing can be
+// chosen. Backing out of the versions returns to the search results
+fn pick_release(
+    client: &Client,
+    user_data: &UserData,
+    results: &[SearchResult],
+) -> Result<Option<String>> {
+    let mut picker = Picker::new(
+        "Search Results",
+        RESULT_COLUMNS.to_vec(),
+        result_rows(results),
+    );
+
+    loop {
+        let result = match picker.run()? {
+            Some(i) => &results[i],
+            None => return Ok(None),
+        };
+
+        match result.kind {
+            ResultKind::Release => return Ok(Some(result.resource_url.clone())),
+            ResultKind::Master => {
+                let title = format!("Versions of {}", result.title);
+                if let Some(url) = pick_version(client, user_data, &result.resource_url, &title)? {
+                    return Ok(Some(url));
+                }
+            }
+        }
+    }
+}
+
+// Lets the user pick one of a master's versions, returning the chosen release's url
+fn pick_version(
+    client: &Client,
+    user_data: &UserData,
+    master_url: &str,
+    title: &str,
+) -> Result<Option<String>> {
+    let versions = master_versions(client, user_data, master_url)?;
+    let mut picker = Picker::new(title, RESULT_COLUMNS.to_vec(), result_rows(&versions));
+
+    Ok(picker.run()?.map(|i| versions[i].resource_url.clone()))
+}
+
+// Converts search results into the picker's rows, following the order of RESULT_COLUMNS
+fn result_rows(results: &[SearchResult]) -> Vec<Vec<String>> {
+    results
+        .iter()
+        .map(|result| {
+            let kind = match result.kind {
+                ResultKind::Master => "Master",
+                ResultKind::Release => "Release",
+            };
+            vec![
+                result.title.clone(),
+                kind.to_string(),
+                result.year.clone(),
+                result.country.clone(),
+                result.format.clone(),
+                result.label.clone(),
+                result.catno.clone(),
+            ]
+        })
+        .collect()
+}
+
+// Prints a given record's details, followed by its tracklist, within an area of the info pane
+// Details that don't fit within the area's rows are left out
+fn print_details(area: Rect, record: &Record) -> Result<()> {
+    let details = [
+        ("Release:", record.year.to_string()),
+        ("Genre:", record.genre.join(" / ")),
+        ("Style:", record.style.join(" / ")),
+        ("Label:", record.label.join(" / ")),
+        ("Country:", record.country.clone()),
+        ("Format:", record.format.clone()),
+    ];
+    // Each detail is followed by a blank row
+    for (i, (label, mut value)) in details.into_iter().enumerate() {
+        let row = area.y + i as u16 * 2;
+        if row >= area.bottom() {
+            return Ok(());
+        }
+        max_len(&mut value, area.width.saturating_sub(9) as usize);
+        print_at((area.x, row), &format!("{:9}{}", label, value))?;
+    }
+
+    // The tracklist takes the rows below its header
+    if area.height > 14 {
+        let width = area.width as usize;
+        print_at((area.x, area.y + 12), &format!("{:^width$}", "Tracklist"))?;
+        print_at(
+            (area.x, area.y + 13),
+            &format!("{:^width$}", "─────────────────────"),
+        )?;
+        print_tracklist(
+            Rect {
+                y: area.y + 14,
+                height: area.height - 14,
+                ..area
+            },
+            record,
+        )?;
+    }
+
+    Ok(())
+}
+
+// Prints a given record's tracklist within an area of the info pane, in as many rows as fit
+fn print_tracklist(area: Rect, record: &Record) -> Result<()> {
     let mut row = 0;
     let mut sides: Vec<String> = Vec::new();
     let mut track = 0;
-    // Iterates until either the end of the tracklist or 15 rows have been drawn
-    while track < record.tracklist.len() && row < 15 {
-        execute!(stdout(), cursor::MoveTo(45, start_row + row))?;
+    // Iterates until either the end of the tracklist or the area's rows have been drawn
+    while track < record.tracklist.len() && row < area.height {
         let current_track = &record.tracklist[track];
         // Extracts the side name (A, B, etc.) from the track data
-        let side = current_track.position.get(0..1).unwrap().to_string();
+        let side = current_track
+            .position
+            .get(0..1)
+            .unwrap_or_default()
+            .to_string();
 
         // If it's a new side, print a "Side X:" header...
         if !sides.contains(&side) {
-            if sides.len() != 0 {
+            if !sides.is_empty() {
                 row += 1;
-                execute!(stdout(), cursor::MoveTo(45, start_row + row))?;
+                if row >= area.height {
+                    break;
+                }
             }
             sides.push(side.clone());
-            print!("Side {}:", side);
+            print_at((area.x, area.y + row), &format!("Side {}:", side))?;
         } else {
             // Otherwise, print the track number, title and duration
             // Ignore track number if it is not given
-            let position = current_track.position.get(1..).unwrap();
-            let mut track_str = if position.len() == 0 {
-                format!("  {:25} {}", current_track.title, current_track.duration)
+            let position = current_track.position.get(1..).unwrap_or_default();
+            let prefix = if position.is_empty() {
+                "  ".to_string()
             } else {
-                format!(
-                    "  {}. {:23} {}",
-                    position, current_track.title, current_track.duration
-                )
+                format!("  {}. ", position)
             };
 
-            // Trim the track_str to fit in the info box
-            if track_str.len() > 33 {
-                let end = track_str.len() - (current_track.duration.len() + 1);
-                let begin = 33 - (current_track.duration.len() + 4);
-                track_str.replace_range(begin..end, "...")
-            }
-
-            print!("{}", track_str);
+            // Trim the title so that the track fits in the info box, with the durations lined up
+            // at its right
+            let title_width = (area.width as usize).saturating_sub(
+                prefix.chars().count() + current_track.duration.chars().count() + 1,
+            );
+            let mut title = current_track.title.clone();
+            max_len(&mut title, title_width);
+
+            let track_str = format!(
+                "{}{:title_width$} {}",
+                prefix, title, current_track.duration
+            );
+            print_at((area.x, area.y + row), &track_str)?;
             track += 1;
         }
         row += 1;
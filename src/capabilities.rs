@@ -0,0 +1,274 @@
+use crate::img_to_ascii::RenderMode;
+use crate::settings::parse_name;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Forces the color support used instead of detecting it, e.g. VINYLLA_COLORS=16 to test how the
+// app looks on a basic terminal
+const COLORS_VAR: &str = "VINYLLA_COLORS";
+
+// The colors the terminal is able to display
+// TrueColor: Any RGB color
+// Palette256: The xterm 256 color palette
+// Palette16: The basic ANSI colors
+// Monochrome: Only the terminal's default colors, e.g. a dumb terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSupport {
+    TrueColor,
+    #[serde(rename = "256")]
+    Palette256,
+    #[serde(rename = "16")]
+    Palette16,
+    Monochrome,
+}
+
+impl ColorSupport {
+    // Detects the terminal's color support from its environment variables
+    // Terminals announce truecolor support through COLORTERM, which isn't always set, so TERM
+    // names that include it are checked as well. Otherwise TERM names the terminal's palette,
+    // e.g. "xterm-256color", and any other terminal but a dumb one has at least the ANSI colors
+    pub fn detect() -> ColorSupport {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        ColorSupport::from_env(&var(COLORS_VAR), &var("COLORTERM"), &var("TERM"))
+    }
+
+    // Works out the color support from the values of COLORS_VAR, COLORTERM and TERM, where an
+    // unset variable is empty. COLORS_VAR is ignored unless it names a color support
+    fn from_env(forced: &str, colorterm: &str, term: &str) -> ColorSupport {
+        if let Some(colors) = ColorSupport::parse(forced) {
+            return colors;
+        }
+
+        if matches!(colorterm, "truecolor" | "24bit")
+            || term.ends_with("-direct")
+            || term.ends_with("-truecolor")
+        {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Palette256
+        } else if term.is_empty() || term == "dumb" {
+            ColorSupport::Monochrome
+        } else {
+            ColorSupport::Palette16
+        }
+    }
+
+    // The names the color supports are written as in the settings
+    pub const NAMES: [(&'static str, ColorSupport); 9] = [
+        ("truecolor", ColorSupport::TrueColor),
+        ("256", ColorSupport::Palette256),
+        ("16", ColorSupport::Palette16),
+        ("none", ColorSupport::Monochrome),
+        ("24bit", ColorSupport::TrueColor),
+        ("256color", ColorSupport::Palette256),
+        ("16color", ColorSupport::Palette16),
+        ("monochrome", ColorSupport::Monochrome),
+        ("mono", ColorSupport::Monochrome),
+    ];
+
+    // Parses a color support's name as it's written in the settings
+    pub fn parse(name: &str) -> Option<ColorSupport> {
+        parse_name(name, &ColorSupport::NAMES)
+    }
+
+    // The render mode that looks best with the colors the terminal can display
+    // Block characters are only recognisable in color, so other terminals get the character ramp
+    pub fn default_render_mode(&self) -> RenderMode {
+        match self {
+            ColorSupport::TrueColor | ColorSupport::Palette256 | ColorSupport::Palette16 => {
+                RenderMode::HalfBlock
+            }
+            ColorSupport::Monochrome => RenderMode::Ascii,
+        }
+    }
+}
+
+// How long to wait for the terminal to answer a capability query
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+// The size of a terminal cell in pixels when the terminal doesn't report it
+const DEFAULT_CELL_SIZE: (u16, u16) = (10, 20);
+
+// The ways the terminal can draw the cover image
+// Kitty: The Kitty graphics protocol, supported by kitty, WezTerm and others
+// Sixel: Sixel graphics, supported by foot, WezTerm, xterm and others
+// Text: The cover is drawn as AsciiArt, for terminals that can't display images
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphicsProtocol {
+    Kitty,
+    Sixel,
+    Text,
+}
+
+impl GraphicsProtocol {
+    // Detects which graphics protocol the terminal supports by querying it
+    // A Kitty graphics query is sent followed by a request for the terminal's device attributes,
+    // which every terminal answers, so that terminals that ignore the Kitty query don't have to be
+    // waited on. Sixel support is listed in the device attributes as attribute 4
+    // Terminals that don't answer at all get text art. Raw mode has to be enabled so that the
+    // answer can be read
+    pub fn detect() -> GraphicsProtocol {
+        let response = query_terminal("\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[c");
+        let response = String::from_utf8_lossy(&response);
+
+        if response.contains("\x1b_Gi=31;OK") {
+            return GraphicsProtocol::Kitty;
+        }
+
+        let attributes = response
+            .split("\x1b[?")
+            .nth(1)
+            .and_then(|attributes| attributes.split('c').next())
+            .unwrap_or_default();
+        if attributes.split(';').any(|attribute| attribute == "4") {
+            GraphicsProtocol::Sixel
+        } else {
+            GraphicsProtocol::Text
+        }
+    }
+
+    // The names the graphics protocols are written as in the settings
+    pub const NAMES: [(&'static str, GraphicsProtocol); 4] = [
+        ("kitty", GraphicsProtocol::Kitty),
+        ("sixel", GraphicsProtocol::Sixel),
+        ("text", GraphicsProtocol::Text),
+        ("off", GraphicsProtocol::Text),
+    ];
+
+    // Parses a graphics protocol's name as it's written in the settings
+    pub fn parse(name: &str) -> Option<GraphicsProtocol> {
+        parse_name(name, &GraphicsProtocol::NAMES)
+    }
+}
+
+// Writes a query to the terminal and reads its answer, until the answer to the device attributes
+// request that ends each query arrives or QUERY_TIMEOUT passes
+// The answer is read straight from stdin rather than through crossterm, which would treat it as
+// key presses
+#[cfg(unix)]
+fn query_terminal(query: &str) -> Vec<u8> {
+    use std::io::Write;
+    use std::time::Instant;
+
+    let mut response = Vec::new();
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+        return response;
+    }
+
+    let mut stdout = std::io::stdout();
+    if stdout
+        .write_all(query.as_bytes())
+        .and_then(|_| stdout.flush())
+        .is_err()
+    {
+        return response;
+    }
+
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    // Loops until the device attributes' answer, "ESC [ ? ... c", has been read
+    while !String::from_utf8_lossy(&response)
+        .split("\x1b[?")
+        .nth(1)
+        .is_some_and(|attributes| attributes.contains('c'))
+    {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut poll_fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if remaining.is_zero()
+            || unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as i32) } <= 0
+        {
+            break;
+        }
+
+        let mut buffer = [0u8; 256];
+        let read = unsafe {
+            libc::read(
+                libc::STDIN_FILENO,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        };
+        if read <= 0 {
+            break;
+        }
+        response.extend_from_slice(&buffer[..read as usize]);
+    }
+
+    response
+}
+
+#[cfg(not(unix))]
+fn query_terminal(_query: &str) -> Vec<u8> {
+    Vec::new()
+}
+
+// The size of a terminal cell in pixels, as reported by the terminal, used to fit the cover image
+// within the cells of its panel
+#[cfg(unix)]
+pub fn cell_size() -> (u16, u16) {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result != 0 || size.ws_col == 0 || size.ws_row == 0 {
+        return DEFAULT_CELL_SIZE;
+    }
+
+    // Terminals that don't report their size in pixels, or report fewer pixels than cells, would
+    // give cells no width or height
+    match (size.ws_xpixel / size.ws_col, size.ws_ypixel / size.ws_row) {
+        (0, _) | (_, 0) => DEFAULT_CELL_SIZE,
+        cell => cell,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn cell_size() -> (u16, u16) {
+    DEFAULT_CELL_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_colors_from_the_environment() {
+        let cases = [
+            ("", "truecolor", "xterm-256color", ColorSupport::TrueColor),
+            ("", "24bit", "", ColorSupport::TrueColor),
+            ("", "", "xterm-direct", ColorSupport::TrueColor),
+            ("", "", "foot-truecolor", ColorSupport::TrueColor),
+            ("", "", "xterm-256color", ColorSupport::Palette256),
+            ("", "", "xterm", ColorSupport::Palette16),
+            ("", "", "linux", ColorSupport::Palette16),
+            ("", "", "dumb", ColorSupport::Monochrome),
+            ("", "", "", ColorSupport::Monochrome),
+        ];
+        for (forced, colorterm, term, expected) in cases {
+            assert_eq!(
+                ColorSupport::from_env(forced, colorterm, term),
+                expected,
+                "{}",
+                term
+            );
+        }
+    }
+
+    #[test]
+    fn forced_colors_override_detection() {
+        let detect = |forced| ColorSupport::from_env(forced, "truecolor", "xterm-256color");
+        assert_eq!(detect("16"), ColorSupport::Palette16);
+        assert_eq!(detect("256COLOR"), ColorSupport::Palette256);
+        assert_eq!(detect("none"), ColorSupport::Monochrome);
+        // Names that aren't color supports fall back to detecting it
+        assert_eq!(detect("lots"), ColorSupport::TrueColor);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::capabilities::ColorSupport;
+use crate::img_to_ascii::{Adjustments, AsciiArt, RenderMode, ADJUSTMENTS, CELL_ASPECT};
+
+use std::io::{IsTerminal, Read, Result};
+
+// The width of art printed somewhere other than a terminal, e.g. into a file
+const DEFAULT_COLUMNS: usize = 80;
+
+const ASCII_USAGE: &str = "\
+Usage: vinylla ascii [options] <image path, or - to read stdin>
+
+Prints an image as text art.
+
+Options:
+  -c, --columns <n>   The art's width in characters (default: the terminal's width, or 80)
+  -r, --rows <n>      The art's height in characters (default: the image's proportions)
+  -m, --mode <mode>   block, halfblock, ascii, braille or lineart (default: picked from the colors)
+      --color <c>     truecolor, 256, 16 or none (default: detected from the terminal)
+      --linear, --contrast, --brightness, --saturation, --levels, --edges, --fill, --tint <value>
+                      Adjust the image like the app's Set command does
+  -h, --help          Prints this help";
+
+// The options of the ascii subcommand
+// path: The image's path, or "-" for stdin
+// columns, rows: The art's size, left to be worked out from the terminal and image when unset
+// mode, colors: How the art is drawn, left to be worked out from the terminal when unset
+struct AsciiOptions {
+    path: String,
+    columns: Option<usize>,
+    rows: Option<usize>,
+    mode: Option<RenderMode>,
+    colors: Option<ColorSupport>,
+    adjustments: Adjustments,
+}
+
+// Runs the ascii subcommand, e.g. "vinylla ascii -c 60 --mode braille cover.jpg", which prints an
+// image as AsciiArt to stdout without starting the app
+// Invalid arguments print the usage to stderr and exit with status 2, and images that can't be
+// read exit with status 1
+pub fn run_ascii(args: &[String]) -> Result<()> {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", ASCII_USAGE);
+        return Ok(());
+    }
+
+    let options = match parse_ascii_options(args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, ASCII_USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let image = if options.path == "-" {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        image::load_from_memory(&data)
+    } else {
+        image::open(&options.path)
+    };
+    let image = match image {
+        Ok(image) => image,
+        Err(error) => {
+            eprintln!("Couldn't read the image {}: {}", options.path, error);
+            std::process::exit(1);
+        }
+    };
+
+    // The art fills the terminal's width by default, and is as tall as keeps the image's
+    // proportions
+    let columns = options
+        .columns
+        .unwrap_or_else(|| match std::io::stdout().is_terminal() {
+            true => crossterm::terminal::size().map_or(DEFAULT_COLUMNS, |(cols, _)| cols as usize),
+            false => DEFAULT_COLUMNS,
+        });
+    let rows = options.rows.unwrap_or_else(|| {
+        let aspect = image.height() as f32 / image.width().max(1) as f32;
+        ((columns as f32 * aspect / CELL_ASPECT).round() as usize).max(1)
+    });
+
+    let colors = options.colors.unwrap_or_else(ColorSupport::detect);
+    let mode = options.mode.unwrap_or_else(|| colors.default_render_mode());
+
+    AsciiArt::from_image_with_mode(image, columns, rows, mode, &options.adjustments)?.print(colors)
+}
+
+// Parses the ascii subcommand's arguments, returning a message describing the first invalid one
+fn parse_ascii_options(args: &[String]) -> std::result::Result<AsciiOptions, String> {
+    let mut options = AsciiOptions {
+        path: String::new(),
+        columns: None,
+        rows: None,
+        mode: None,
+        colors: None,
+        adjustments: Adjustments::default(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // Options that aren't followed by a value are the image's path
+        if arg == "-" || !arg.starts_with('-') {
+            if !options.path.is_empty() {
+                return Err(format!("Only one image can be converted, found '{}'", arg));
+            }
+            options.path = arg.clone();
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("'{}' needs a value", arg))?;
+        let invalid = || format!("'{}' is not a valid value for {}", value, arg);
+        let size = || value.parse::<usize>().ok().filter(|n| *n > 0);
+
+        match arg.as_str() {
+            "-c" | "--columns" => options.columns = Some(size().ok_or_else(invalid)?),
+            "-r" | "--rows" => options.rows = Some(size().ok_or_else(invalid)?),
+            "-m" | "--mode" => options.mode = Some(RenderMode::parse(value).ok_or_else(invalid)?),
+            "--color" => options.colors = Some(ColorSupport::parse(value).ok_or_else(invalid)?),
+            // The other long options are the adjustments, named as they are in the Set command
+            _ => match arg.strip_prefix("--") {
+                Some(name) if ADJUSTMENTS.contains(&name) => {
+                    options.adjustments.set(name, value).ok_or_else(invalid)?
+                }
+                _ => return Err(format!("Unknown option {}", arg)),
+            },
+        }
+    }
+
+    if options.path.is_empty() {
+        return Err("No image was given".to_string());
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> std::result::Result<AsciiOptions, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_ascii_options(&args)
+    }
+
+    #[test]
+    fn parses_the_path_and_options() {
+        let options =
+            parse(&["-c", "60", "cover.jpg", "--rows", "20", "--contrast", "1.5"]).unwrap();
+        assert_eq!(options.path, "cover.jpg");
+        assert_eq!(options.columns, Some(60));
+        assert_eq!(options.rows, Some(20));
+        assert_eq!(options.adjustments.contrast, 1.5);
+
+        // A lone "-" is stdin rather than an option
+        let options = parse(&["-m", "braille", "-"]).unwrap();
+        assert_eq!(options.path, "-");
+        assert_eq!(options.mode, Some(RenderMode::Braille));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        for size in ["0", "-3", "wide"] {
+            assert_eq!(
+                parse(&["-c", size, "cover.jpg"]).err(),
+                Some(format!("'{}' is not a valid value for -c", size))
+            );
+        }
+        assert_eq!(
+            parse(&["cover.jpg", "--rows"]).err(),
+            Some("'--rows' needs a value".to_string())
+        );
+    }
+
+    #[test]
+    fn tells_unknown_options_from_invalid_values() {
+        assert_eq!(
+            parse(&["--contrast", "abc", "cover.jpg"]).err(),
+            Some("'abc' is not a valid value for --contrast".to_string())
+        );
+        assert_eq!(
+            parse(&["--foo", "1", "cover.jpg"]).err(),
+            Some("Unknown option --foo".to_string())
+        );
+        assert_eq!(
+            parse(&["-x", "1", "cover.jpg"]).err(),
+            Some("Unknown option -x".to_string())
+        );
+    }
+
+    #[test]
+    fn needs_exactly_one_image() {
+        assert_eq!(
+            parse(&["-c", "60"]).err(),
+            Some("No image was given".to_string())
+        );
+        assert_eq!(
+            parse(&["a.jpg", "b.jpg"]).err(),
+            Some("Only one image can be converted, found 'b.jpg'".to_string())
+        );
+    }
+}
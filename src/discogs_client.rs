@@ -2,11 +2,19 @@
 // the repository. If you wish to extend the project you'll have to create your own Discogs
 // developer tokens, which is linked here: https://www.discogs.com/developers#page:authentication
 use crate::config::{CONSUMER_KEY, CONSUMER_SECRET};
+use crate::record::process_artist;
 
-use crossterm::{cursor, execute, terminal};
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, Response};
+use reqwest::{Method, StatusCode, Url};
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How long to wait before retrying a request after hitting Discogs' rate limit, unless Discogs
+// says how long to wait in the response's Retry-After header
+const RATE_LIMIT_WAIT: Duration = Duration::from_secs(5);
+// How many times a request that hits the rate limit is retried before giving up
+const MAX_RATE_LIMIT_RETRIES: u32 = 12;
 
 // Struct containing the Discogs APIs user authentication tokens
 // These get serialized and saved to a file after logging in to keep the user's session across app
@@ -103,12 +111,18 @@ pub fn create_headers(
 
 // Authenicates a user following the authentication process outlined on the Discogs API page
 // This function is called when the user executes the 'Login' command
-pub(crate) fn authenticate(client: &Client) -> Result<UserData, reqwest::Error> {
+// read_verifier shows the user the link where they authorize the application, and returns the
+// code they paste back. Its errors are returned rather than sending an empty code to Discogs
+pub(crate) fn authenticate(
+    client: &Client,
+    read_verifier: impl FnOnce(&str) -> std::io::Result<String>,
+) -> std::io::Result<UserData> {
     let response = client
         .get("https://api.discogs.com/oauth/request_token")
         .headers(create_headers(RequestType::RequestURL, None, None, None))
-        .send()?
-        .text()?;
+        .send()
+        .and_then(|response| response.text())
+        .map_err(to_io_error)?;
 
     // Retrieve the authentication tokens from the GET response
     let mut oauth_token = response.replace("oauth_token=", "");
@@ -117,22 +131,12 @@ pub(crate) fn authenticate(client: &Client) -> Result<UserData, reqwest::Error>
         [(response.find("&oauth_token_secret=").unwrap() + "&oauth_token_secret=".len())..]
         .to_string();
 
-    // Prompts the user to authorize the application on their browser through a link...
-    print!("║ Please authorize the application at the link below.                                                                            ║\r\n");
-    print!(
-        "║ https://discogs.com/oauth/authorize?oauth_token={:79}║",
+    // Prompts the user to authorize the application on their browser through a link, and to paste
+    // in the code they're given
+    let verifier = read_verifier(&format!(
+        "https://discogs.com/oauth/authorize?oauth_token={}",
         oauth_token
-    );
-    print!("║ Then paste the code here:                                                                                                      ║\r\n");
-    execute!(std::io::stdout(), cursor::MoveTo(28, 36)).unwrap();
-    terminal::disable_raw_mode().unwrap();
-
-    // ... retrieving user input after disabling raw mode to paste in the code
-    let mut verifier = String::new();
-    std::io::stdin().read_line(&mut verifier).unwrap();
-
-    execute!(std::io::stdout(), cursor::MoveTo(0, 37)).unwrap();
-    terminal::enable_raw_mode().unwrap();
+    ))?;
 
     // Then sends another GET request to the api with the user's code..
     let response = client
@@ -143,8 +147,9 @@ pub(crate) fn authenticate(client: &Client) -> Result<UserData, reqwest::Error>
             Some(oauth_token_secret),
             Some(verifier.trim_end()),
         ))
-        .send()?
-        .text()?;
+        .send()
+        .and_then(|response| response.text())
+        .map_err(to_io_error)?;
 
     // ... to then retrieve the users authentication tokens
     let mut oauth_token = response.replace("oauth_token=", "");
@@ -162,18 +167,475 @@ pub(crate) fn authenticate(client: &Client) -> Result<UserData, reqwest::Error>
 }
 
 // A utility function to more easily make an authenticated request
-pub(crate) fn make_auth_request(client: &Client, user_data: &UserData, url: String) -> reqwest::Result<String> {
-        let response = client 
-            .get(url)
-            .headers(create_headers(
-                RequestType::RequestAuthorized,
-                Some(user_data.oauth_token.clone()),
-                Some(user_data.oauth_token_secret.clone()),
-                None,
-            ))
-            .send()?
-            .text()?;
-
-        Ok(response)
+pub(crate) fn make_auth_request(
+    client: &Client,
+    user_data: &UserData,
+    method: Method,
+    url: &str,
+) -> reqwest::Result<Response> {
+    client
+        .request(method, url)
+        .headers(create_headers(
+            RequestType::RequestAuthorized,
+            Some(user_data.oauth_token.clone()),
+            Some(user_data.oauth_token_secret.clone()),
+            None,
+        ))
+        .send()
+}
+
+// Makes an authenticated GET request and parses its json response
+pub(crate) fn get_json(client: &Client, user_data: &UserData, url: &str) -> std::io::Result<Value> {
+    send_json(client, user_data, Method::GET, url)
+}
+
+// Makes an authenticated request and parses its json response, which is Null if it has no body
+// Discogs allows 60 authenticated requests per minute, so requests that hit the rate limit are
+// retried after a short wait, up to MAX_RATE_LIMIT_RETRIES times. Any other error status is
+// returned as an error with Discogs' message
+pub(crate) fn send_json(
+    client: &Client,
+    user_data: &UserData,
+    method: Method,
+    url: &str,
+) -> std::io::Result<Value> {
+    let mut retries = 0;
+    loop {
+        let response =
+            make_auth_request(client, user_data, method.clone(), url).map_err(to_io_error)?;
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            if retries == MAX_RATE_LIMIT_RETRIES {
+                return Err(std::io::Error::other(
+                    "Discogs' rate limit was reached too many times, try again later",
+                ));
+            }
+            retries += 1;
+            std::thread::sleep(retry_after(&response).unwrap_or(RATE_LIMIT_WAIT));
+            continue;
+        }
+
+        let text = response.text().map_err(to_io_error)?;
+        let data: Value = if text.trim().is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&text)?
+        };
+        if !status.is_success() {
+            let message = data["message"].as_str().unwrap_or("Discogs request failed");
+            return Err(std::io::Error::other(format!("{} ({})", message, status)));
+        }
+
+        return Ok(data);
+    }
+}
+
+// How long a rate limited response asks to wait before retrying, from its Retry-After header
+// Only the header's number of seconds form is read, which is the one Discogs sends
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+// Whether a search result points to a master release (which groups all of an album's versions)
+// or to a specific release (a single pressing)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ResultKind {
+    Master,
+    Release,
+}
+
+// A single entry of a database search or of a master's versions list, holding the information
+// needed to tell the pressings apart
+#[derive(Clone, Debug)]
+pub(crate) struct SearchResult {
+    pub kind: ResultKind,
+    pub title: String,
+    pub year: String,
+    pub country: String,
+    pub format: String,
+    pub label: String,
+    pub catno: String,
+    pub resource_url: String,
+}
+
+impl SearchResult {
+    // Parses an entry of the "results" list returned by the database search endpoint
+    // Returns None for the artist and label results that the search can also return
+    fn from_search(result: &Value) -> Option<SearchResult> {
+        let kind = match result["type"].as_str()? {
+            "master" => ResultKind::Master,
+            "release" => ResultKind::Release,
+            _ => return None,
+        };
+
+        Some(SearchResult {
+            kind,
+            title: value_to_string(&result["title"]),
+            year: value_to_string(&result["year"]),
+            country: value_to_string(&result["country"]),
+            format: value_to_string(&result["format"]),
+            label: value_to_string(&result["label"]),
+            catno: value_to_string(&result["catno"]),
+            resource_url: result["resource_url"].as_str()?.to_string(),
+        })
+    }
+
+    // Parses an entry of the "versions" list returned by a master's versions endpoint
+    fn from_version(version: &Value) -> Option<SearchResult> {
+        Some(SearchResult {
+            kind: ResultKind::Release,
+            title: value_to_string(&version["title"]),
+            year: value_to_string(&version["released"]),
+            country: value_to_string(&version["country"]),
+            format: value_to_string(&version["format"]),
+            label: value_to_string(&version["label"]),
+            catno: value_to_string(&version["catno"]),
+            resource_url: version["resource_url"].as_str()?.to_string(),
+        })
+    }
+}
+
+// Searches the Discogs database for the masters and releases matching the query
+pub(crate) fn search(
+    client: &Client,
+    user_data: &UserData,
+    query: &str,
+) -> std::io::Result<Vec<SearchResult>> {
+    let search = get_json(client, user_data, search_url(query).as_str())?;
+
+    Ok(match search["results"].as_array() {
+        Some(results) => results
+            .iter()
+            .filter_map(SearchResult::from_search)
+            .collect(),
+        None => Vec::new(),
+    })
+}
+
+// The url that searches the database for the given text, which is percent encoded so that
+// characters like '&' or '#' in an artist or title are searched for rather than ending the query
+// The base url is always valid, so building it can't fail
+fn search_url(query: &str) -> Url {
+    Url::parse_with_params(
+        "https://api.discogs.com/database/search",
+        &[("q", query), ("per_page", "100")],
+    )
+    .unwrap()
+}
+
+// Retrieves the list of versions (pressings) of a master release from its resource url
+pub(crate) fn master_versions(
+    client: &Client,
+    user_data: &UserData,
+    master_url: &str,
+) -> std::io::Result<Vec<SearchResult>> {
+    let versions_url = format!("{}/versions?per_page=100", master_url);
+    let versions = get_json(client, user_data, &versions_url)?;
+
+    Ok(match versions["versions"].as_array() {
+        Some(versions) => versions
+            .iter()
+            .filter_map(SearchResult::from_version)
+            .collect(),
+        None => Vec::new(),
+    })
+}
+
+// Retrieves a release's full information (tracklist, images, etc.) from its resource url
+pub(crate) fn get_release(
+    client: &Client,
+    user_data: &UserData,
+    release_url: &str,
+) -> std::io::Result<Value> {
+    get_json(client, user_data, release_url)
+}
+
+// Retrieves the username of the logged in user from the identity endpoint
+pub(crate) fn get_username(client: &Client, user_data: &UserData) -> std::io::Result<String> {
+    let identity = get_json(client, user_data, "https://api.discogs.com/oauth/identity")?;
+
+    match identity["username"].as_str() {
+        Some(username) => Ok(username.to_string()),
+        None => Err(std::io::Error::other("Discogs did not return a username")),
+    }
+}
+
+// A release in the user's Discogs collection, as listed by the collection endpoints
+// The artist and title are taken from the listing's basic information, so that the release can be
+// matched against the local collection without fetching its full data
+#[derive(Clone, Debug)]
+pub(crate) struct CollectionItem {
+    pub artist: String,
+    pub title: String,
+    pub release_id: u64,
+    pub instance: CollectionInstance,
+    pub resource_url: String,
+}
+
+// Identifies a copy of a release in a user's Discogs collection, since a collection can hold the
+// same release more than once
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct CollectionInstance {
+    pub folder_id: u64,
+    pub instance_id: u64,
+}
+
+// Retrieves every release in the user's Discogs collection, one page at a time
+// on_page is called after each page is retrieved with the number of pages loaded and the total
+pub(crate) fn get_collection(
+    client: &Client,
+    user_data: &UserData,
+    username: &str,
+    mut on_page: impl FnMut(u64, u64) -> std::io::Result<()>,
+) -> std::io::Result<Vec<CollectionItem>> {
+    let mut items = Vec::new();
+    let mut page = 1;
+
+    loop {
+        let page_url = format!(
+            "https://api.discogs.com/users/{}/collection/folders/0/releases?page={}&per_page=100",
+            username, page
+        );
+        let data = get_json(client, user_data, &page_url)?;
+
+        if let Some(releases) = data["releases"].as_array() {
+            for release in releases {
+                let info = &release["basic_information"];
+                let (resource_url, release_id, folder_id, instance_id) = match (
+                    info["resource_url"].as_str(),
+                    release["id"].as_u64(),
+                    release["folder_id"].as_u64(),
+                    release["instance_id"].as_u64(),
+                ) {
+                    (Some(url), Some(release_id), Some(folder_id), Some(instance_id)) => {
+                        (url.to_string(), release_id, folder_id, instance_id)
+                    }
+                    _ => continue,
+                };
+
+                items.push(CollectionItem {
+                    artist: process_artist(&info["artists"][0]["name"]),
+                    title: value_to_string(&info["title"]),
+                    release_id,
+                    instance: CollectionInstance {
+                        folder_id,
+                        instance_id,
+                    },
+                    resource_url,
+                });
+            }
+        }
+
+        let pages = data["pagination"]["pages"].as_u64().unwrap_or(1);
+        on_page(page, pages)?;
+        if page >= pages {
+            break;
+        }
+        page += 1;
+    }
+
+    Ok(items)
+}
+
+// Adds a release to the user's Discogs collection, returning the new copy's instance
+// Releases are added to the "Uncategorized" folder, as the "All" folder can't be added to
+pub(crate) fn add_to_collection(
+    client: &Client,
+    user_data: &UserData,
+    username: &str,
+    release_id: u64,
+) -> std::io::Result<CollectionInstance> {
+    let folder_id = 1;
+    let url = format!(
+        "https://api.discogs.com/users/{}/collection/folders/{}/releases/{}",
+        username, folder_id, release_id
+    );
+    let data = send_json(client, user_data, Method::POST, &url)?;
+
+    match data["instance_id"].as_u64() {
+        Some(instance_id) => Ok(CollectionInstance {
+            folder_id,
+            instance_id,
+        }),
+        None => Err(std::io::Error::other(
+            "Discogs did not return the added release",
+        )),
+    }
+}
+
+// Deletes a copy of a release from the user's Discogs collection
+pub(crate) fn remove_from_collection(
+    client: &Client,
+    user_data: &UserData,
+    username: &str,
+    release_id: u64,
+    instance: CollectionInstance,
+) -> std::io::Result<()> {
+    let url = format!(
+        "https://api.discogs.com/users/{}/collection/folders/{}/releases/{}/instances/{}",
+        username, instance.folder_id, release_id, instance.instance_id
+    );
+    send_json(client, user_data, Method::DELETE, &url)?;
+
+    Ok(())
+}
+
+// A reference to a Discogs database entry, given by the user as an ID or a pasted url
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum DiscogsRef {
+    Release(u64),
+    Master(u64),
+}
+
+impl DiscogsRef {
+    // Parses a release ID or a Discogs url into a DiscogsRef
+    // Accepts plain release IDs ("1234"), the IDs as printed on Discogs pages ("r1234", "[m567]"),
+    // and website or API urls such as "https://www.discogs.com/release/1234-Artist-Title" or
+    // "https://www.discogs.com/master/567-Artist-Title"
+    pub fn parse(input: &str) -> Option<DiscogsRef> {
+        let input = input.trim().trim_start_matches('[').trim_end_matches(']');
+
+        if let Ok(id) = input.parse::<u64>() {
+            return Some(DiscogsRef::Release(id));
+        }
+
+        // Urls contain the entry's type as a path segment followed by the ID and an optional slug
+        if input.contains('/') {
+            let mut segments = input.split(['/', '?', '#']);
+            while let Some(segment) = segments.next() {
+                let make_ref = match segment {
+                    "release" | "releases" => DiscogsRef::Release,
+                    "master" | "masters" => DiscogsRef::Master,
+                    _ => continue,
+                };
+                return segments.next().and_then(leading_id).map(make_ref);
+            }
+            return None;
+        }
+
+        // Otherwise the input is an ID prefixed with its type
+        let mut chars = input.chars();
+        let make_ref = match chars.next() {
+            Some('r' | 'R') => DiscogsRef::Release,
+            Some('m' | 'M') => DiscogsRef::Master,
+            _ => return None,
+        };
+        chars.as_str().parse().ok().map(make_ref)
+    }
+
+    // The API url of the referenced release or master
+    pub fn api_url(&self) -> String {
+        match self {
+            DiscogsRef::Release(id) => format!("https://api.discogs.com/releases/{}", id),
+            DiscogsRef::Master(id) => format!("https://api.discogs.com/masters/{}", id),
+        }
+    }
+}
+
+// Parses the ID at the start of a url segment, e.g. "1234-Artist-Title" -> 1234
+fn leading_id(segment: &str) -> Option<u64> {
+    let end = segment
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(segment.len());
+    segment[..end].parse().ok()
+}
+
+// Flattens a json value into a displayable string
+// Lists of values, like a release's formats or labels, are joined with commas
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Array(values) => values
+            .iter()
+            .map(value_to_string)
+            .collect::<Vec<String>>()
+            .join(", "),
+        _ => String::new(),
+    }
+}
+
+// Wraps a reqwest error into an io error so that it can be propagated through the app
+pub(crate) fn to_io_error(error: reqwest::Error) -> std::io::Error {
+    std::io::Error::other(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bare_ids_as_releases() {
+        assert_eq!(DiscogsRef::parse("1234"), Some(DiscogsRef::Release(1234)));
+        assert_eq!(
+            DiscogsRef::parse("  1234 \n"),
+            Some(DiscogsRef::Release(1234))
+        );
+    }
+
+    #[test]
+    fn parses_prefixed_ids() {
+        assert_eq!(DiscogsRef::parse("r123"), Some(DiscogsRef::Release(123)));
+        assert_eq!(DiscogsRef::parse("m123"), Some(DiscogsRef::Master(123)));
+        assert_eq!(DiscogsRef::parse("[r123]"), Some(DiscogsRef::Release(123)));
+        assert_eq!(DiscogsRef::parse("[M567]"), Some(DiscogsRef::Master(567)));
     }
 
+    #[test]
+    fn parses_urls() {
+        assert_eq!(
+            DiscogsRef::parse("https://www.discogs.com/release/1234-Artist-Title"),
+            Some(DiscogsRef::Release(1234))
+        );
+        assert_eq!(
+            DiscogsRef::parse("https://www.discogs.com/master/567-Artist-Title?ev=rr#images"),
+            Some(DiscogsRef::Master(567))
+        );
+        assert_eq!(
+            DiscogsRef::parse("https://www.discogs.com/release/1234?ev=rr"),
+            Some(DiscogsRef::Release(1234))
+        );
+        assert_eq!(
+            DiscogsRef::parse("https://api.discogs.com/releases/1234"),
+            Some(DiscogsRef::Release(1234))
+        );
+        assert_eq!(
+            DiscogsRef::parse("https://www.discogs.com/fr/master/567-Artist-Title"),
+            Some(DiscogsRef::Master(567))
+        );
+    }
+
+    #[test]
+    fn rejects_other_input() {
+        assert_eq!(DiscogsRef::parse(""), None);
+        assert_eq!(DiscogsRef::parse("abc"), None);
+        assert_eq!(DiscogsRef::parse("x123"), None);
+        assert_eq!(DiscogsRef::parse("r"), None);
+        assert_eq!(DiscogsRef::parse("r12a"), None);
+        assert_eq!(
+            DiscogsRef::parse("https://www.discogs.com/artist/45-Artist"),
+            None
+        );
+        assert_eq!(
+            DiscogsRef::parse("https://www.discogs.com/release/Artist-Title"),
+            None
+        );
+    }
+
+    #[test]
+    fn encodes_search_queries() {
+        assert_eq!(
+            search_url("Simon & Garfunkel #1 + ?").as_str(),
+            "https://api.discogs.com/database/search?q=Simon+%26+Garfunkel+%231+%2B+%3F&per_page=100"
+        );
+    }
+}
@@ -0,0 +1,385 @@
+use crate::capabilities::ColorSupport;
+use crate::img_to_ascii::{AsciiArt, Textel, BRAILLE_DOTS, BRIGHTNESS_RAMP};
+use crate::record::Record;
+use crate::settings::parse_name;
+
+use image::{Rgb, RgbImage};
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+// Where record cards are exported to when no path is given
+const EXPORTS_PATH: &str = "data/exports";
+// The number of columns between a card's cover art and the record's information
+const CARD_GAP: usize = 3;
+// The size in pixels of each textel in PNG exports, which are as tall as terminal cells are
+// compared to their width
+const PNG_CELL_SIZE: (u32, u32) = (8, 16);
+// The background and text colors of exported cards, used wherever the textels don't set them
+const CARD_BACKGROUND: [u8; 3] = [0, 0, 0];
+const CARD_FOREGROUND: [u8; 3] = [229, 229, 229];
+
+// The file formats a record card can be exported as
+// Ansi: Text with the escape codes of the art's colors, which is replayed with `cat`
+// Html: A <pre> block with a colored span for each run of same colored textels
+// Png: An image of the art's textel grid, with each textel drawn as a block of pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Ansi,
+    Html,
+    Png,
+}
+
+impl ExportFormat {
+    // The names of the formats, which are also their file extensions
+    pub const NAMES: [(&'static str, ExportFormat); 5] = [
+        ("ansi", ExportFormat::Ansi),
+        ("html", ExportFormat::Html),
+        ("png", ExportFormat::Png),
+        ("ans", ExportFormat::Ansi),
+        ("htm", ExportFormat::Html),
+    ];
+
+    // Parses a format's name, which is also its file extension
+    pub fn parse(name: &str) -> Option<ExportFormat> {
+        parse_name(name, &ExportFormat::NAMES)
+    }
+
+    // Finds the format a path is exported as from its extension
+    pub fn from_path(path: &str) -> Option<ExportFormat> {
+        Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(ExportFormat::parse)
+    }
+
+    fn extension(&self) -> &str {
+        match self {
+            ExportFormat::Ansi => "ans",
+            ExportFormat::Html => "html",
+            ExportFormat::Png => "png",
+        }
+    }
+}
+
+// Exports a record's card, its cover art with its information beside it, and returns the path it
+// was written to
+// Cards without a path are written to EXPORTS_PATH, named after the record. ANSI cards are printed
+// in the given colors, while HTML and PNG cards keep the textels' own colors since they aren't
+// limited by the terminal. PNG cards only hold the art, as the information would need a font to be
+// drawn
+pub fn export_card(
+    record: &Record,
+    art: &AsciiArt,
+    format: ExportFormat,
+    colors: ColorSupport,
+    path: Option<&str>,
+) -> Result<PathBuf> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            std::fs::create_dir_all(EXPORTS_PATH)?;
+            Path::new(EXPORTS_PATH).join(format!(
+                "{}.{}",
+                file_name(&record.display_title()),
+                format.extension()
+            ))
+        }
+    };
+
+    match format {
+        ExportFormat::Ansi => std::fs::write(&path, ansi_card(record, art, colors)?)?,
+        ExportFormat::Html => std::fs::write(&path, html_card(record, art))?,
+        ExportFormat::Png => png_card(art).save(&path).map_err(std::io::Error::other)?,
+    }
+
+    Ok(path)
+}
+
+// The lines of a record's information as they're shown beside its art
+fn info_lines(record: &Record) -> Vec<String> {
+    let mut lines = vec![
+        record.display_title(),
+        String::new(),
+        format!("{:9}{}", "Release:", record.year),
+        format!("{:9}{}", "Genre:", record.genre.join(" / ")),
+        format!("{:9}{}", "Style:", record.style.join(" / ")),
+        format!("{:9}{}", "Label:", record.label.join(" / ")),
+        format!("{:9}{}", "Country:", record.country),
+        format!("{:9}{}", "Format:", record.format),
+    ];
+
+    if !record.tracklist.is_empty() {
+        lines.push(String::new());
+        lines.push("Tracklist".to_string());
+        for track in record.tracklist.iter() {
+            let line = format!("{:>4}  {}  {}", track.position, track.title, track.duration);
+            lines.push(line.trim_end().to_string());
+        }
+    }
+
+    lines
+}
+
+// Writes the card as text with escape codes, with the art's rows on the left and the information
+// on the right
+fn ansi_card(record: &Record, art: &AsciiArt, colors: ColorSupport) -> Result<String> {
+    let rows = art.ansi_rows(colors)?;
+    let info = info_lines(record);
+
+    let mut card = String::new();
+    for i in 0..rows.len().max(info.len()) {
+        match rows.get(i) {
+            Some(row) => card.push_str(row),
+            None => card.push_str(&" ".repeat(art.width())),
+        }
+        if let Some(line) = info.get(i) {
+            card.push_str(&" ".repeat(CARD_GAP));
+            card.push_str(line);
+        }
+        card.push('\n');
+    }
+
+    Ok(card)
+}
+
+// Writes the card as a self-contained HTML snippet, laid out like the ANSI card
+// The lines are set to the height of the font so that block characters join up like they do in the
+// terminal
+fn html_card(record: &Record, art: &AsciiArt) -> String {
+    let rows: Vec<&[Textel]> = art.rows().collect();
+    let info = info_lines(record);
+
+    let mut html = format!(
+        "<pre style=\"display: inline-block; margin: 0; padding: 1em; line-height: 1; \
+        font-family: monospace; background: {}; color: {}\">\n",
+        hex_color(CARD_BACKGROUND),
+        hex_color(CARD_FOREGROUND)
+    );
+    for i in 0..rows.len().max(info.len()) {
+        match rows.get(i) {
+            Some(row) => push_html_row(&mut html, row),
+            None => html.push_str(&" ".repeat(art.width())),
+        }
+        if let Some(line) = info.get(i) {
+            html.push_str(&" ".repeat(CARD_GAP));
+            html.push_str(&escape_html(line));
+        }
+        html.push('\n');
+    }
+    html.push_str("</pre>\n");
+
+    html
+}
+
+// Appends a row of textels as spans, each holding a run of textels of the same colors
+fn push_html_row(html: &mut String, row: &[Textel]) {
+    for run in row.chunk_by(|a, b| (a.color, a.background) == (b.color, b.background)) {
+        let text: String = run.iter().map(|textel| textel.char).collect();
+        let style = match run[0].background {
+            Some(background) => format!(
+                "color: {}; background: {}",
+                hex_color(run[0].color),
+                hex_color(background)
+            ),
+            None => format!("color: {}", hex_color(run[0].color)),
+        };
+        html.push_str(&format!(
+            "<span style=\"{}\">{}</span>",
+            style,
+            escape_html(&text)
+        ));
+    }
+}
+
+// Draws the art's textels as an image, each as a block of PNG_CELL_SIZE pixels
+fn png_card(art: &AsciiArt) -> RgbImage {
+    let (cell_w, cell_h) = PNG_CELL_SIZE;
+    let mut image = RgbImage::from_pixel(
+        art.width() as u32 * cell_w,
+        art.height() as u32 * cell_h,
+        Rgb(CARD_BACKGROUND),
+    );
+
+    for (y, row) in art.rows().enumerate() {
+        for (x, textel) in row.iter().enumerate() {
+            for py in 0..cell_h {
+                for px in 0..cell_w {
+                    image.put_pixel(
+                        x as u32 * cell_w + px,
+                        y as u32 * cell_h + py,
+                        Rgb(textel_pixel(textel, px, py)),
+                    );
+                }
+            }
+        }
+    }
+
+    image
+}
+
+// The color of a pixel within a textel's block, drawn to look like the textel's character
+// Block characters fill the parts of the block they cover, Braille characters have a square for
+// each of their raised dots, and line art's characters are drawn as lines. Other characters, such
+// as those of the brightness ramp, can't be drawn without a font, so the block is filled with
+// their color as strongly as they are bright
+fn textel_pixel(textel: &Textel, px: u32, py: u32) -> [u8; 3] {
+    let (cell_w, cell_h) = PNG_CELL_SIZE;
+    let background = textel.background.unwrap_or(CARD_BACKGROUND);
+    let blend = |amount: f32| -> [u8; 3] {
+        std::array::from_fn(|c| {
+            (textel.color[c] as f32 * amount + background[c] as f32 * (1.0 - amount)).round() as u8
+        })
+    };
+
+    match textel.char {
+        ' ' => background,
+        '█' => textel.color,
+        '▀' if py < cell_h / 2 => textel.color,
+        '▄' if py >= cell_h / 2 => textel.color,
+        '▀' | '▄' => background,
+        '\u{2800}'..='\u{28ff}' => {
+            let bits = textel.char as u32 - 0x2800;
+            // Each dot sits in the middle of its quarter of the block's width and height
+            let (dot_w, dot_h) = (cell_w / 2, cell_h / 4);
+            let (dot_x, dot_y) = ((px / dot_w) as usize, (py / dot_h) as usize);
+            let in_dot = (dot_w / 4..dot_w * 3 / 4).contains(&(px % dot_w))
+                && (dot_h / 4..dot_h * 3 / 4).contains(&(py % dot_h));
+            if in_dot && bits & BRAILLE_DOTS[dot_y][dot_x] != 0 {
+                textel.color
+            } else {
+                background
+            }
+        }
+        '|' | '/' | '-' | '\\' | '_' => {
+            // How far across the block the line is at the pixel's height, for slanted lines
+            let slant = |y: u32| (y * cell_w / cell_h) as i32;
+            let on_line = match textel.char {
+                '|' => px.abs_diff(cell_w / 2) <= 1,
+                '-' => py.abs_diff(cell_h / 2) <= 1,
+                '_' => py >= cell_h - 2,
+                '/' => (px as i32 - slant(cell_h - 1 - py)).abs() <= 1,
+                _ => (px as i32 - slant(py)).abs() <= 1,
+            };
+            if on_line {
+                textel.color
+            } else {
+                background
+            }
+        }
+        char => match BRIGHTNESS_RAMP.iter().position(|c| *c == char) {
+            Some(i) => blend(i as f32 / (BRIGHTNESS_RAMP.len() - 1) as f32),
+            None => blend(0.5),
+        },
+    }
+}
+
+// Formats a color as a CSS hex color, e.g. "#ff8000"
+fn hex_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// Escapes the characters HTML would read as markup
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Replaces the characters that aren't allowed in file names on some systems
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const GREEN: [u8; 3] = [0, 255, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    fn textel(char: char, color: [u8; 3], background: Option<[u8; 3]>) -> Textel {
+        Textel {
+            char,
+            color,
+            background,
+        }
+    }
+
+    #[test]
+    fn escapes_record_info_in_html() {
+        let record = Record {
+            title: "<Tom & Jerry>".to_string(),
+            artists: vec!["\"Q\"".to_string()],
+            ..Default::default()
+        };
+        let html = html_card(&record, &AsciiArt::new(2, 1));
+
+        assert!(html.contains("&quot;Q&quot; - &lt;Tom &amp; Jerry&gt;"));
+        assert!(!html.contains("<Tom"));
+    }
+
+    #[test]
+    fn merges_same_colored_runs_into_spans() {
+        let row = [
+            textel('█', RED, None),
+            textel('█', RED, None),
+            textel('▀', RED, Some(BLUE)),
+            textel('▀', RED, Some(BLUE)),
+            textel('<', GREEN, None),
+        ];
+        let mut html = String::new();
+        push_html_row(&mut html, &row);
+
+        assert_eq!(
+            html,
+            "<span style=\"color: #ff0000\">██</span>\
+            <span style=\"color: #ff0000; background: #0000ff\">▀▀</span>\
+            <span style=\"color: #00ff00\">&lt;</span>"
+        );
+    }
+
+    #[test]
+    fn draws_block_textels_as_pixels() {
+        let (cell_w, cell_h) = PNG_CELL_SIZE;
+        let full = textel('█', RED, None);
+        assert_eq!(textel_pixel(&full, 0, 0), RED);
+        assert_eq!(textel_pixel(&full, cell_w - 1, cell_h - 1), RED);
+
+        let half = textel('▀', RED, Some(BLUE));
+        assert_eq!(textel_pixel(&half, 0, 0), RED);
+        assert_eq!(textel_pixel(&half, 0, cell_h / 2 - 1), RED);
+        assert_eq!(textel_pixel(&half, 0, cell_h / 2), BLUE);
+        assert_eq!(textel_pixel(&half, cell_w - 1, cell_h - 1), BLUE);
+    }
+
+    #[test]
+    fn draws_braille_dots_as_pixels() {
+        let (dot_w, dot_h) = (PNG_CELL_SIZE.0 / 2, PNG_CELL_SIZE.1 / 4);
+        // The middle of the dot in the given column and row of the character's 2x4 grid
+        let dot = |x: u32, y: u32| (x * dot_w + dot_w / 2, y * dot_h + dot_h / 2);
+
+        // Only the top left dot is raised
+        let one = textel('\u{2801}', GREEN, None);
+        let (px, py) = dot(0, 0);
+        assert_eq!(textel_pixel(&one, px, py), GREEN);
+        let (px, py) = dot(1, 0);
+        assert_eq!(textel_pixel(&one, px, py), CARD_BACKGROUND);
+        let (px, py) = dot(0, 3);
+        assert_eq!(textel_pixel(&one, px, py), CARD_BACKGROUND);
+
+        // Every dot is raised, with gaps between them
+        let all = textel('\u{28ff}', GREEN, None);
+        for (x, y) in [(0, 0), (1, 0), (0, 3), (1, 3)] {
+            let (px, py) = dot(x, y);
+            assert_eq!(textel_pixel(&all, px, py), GREEN);
+        }
+        assert_eq!(textel_pixel(&all, 0, 0), CARD_BACKGROUND);
+    }
+}
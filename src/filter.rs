@@ -0,0 +1,139 @@
+use crate::record::Record;
+
+// An incremental filter over the records list, which narrows it to the records matching what's
+// typed after pressing '/'
+// query: The text the records are matched against
+// editing: Whether keys are typed into the query, rather than moving through the matches
+pub struct Filter {
+    pub query: String,
+    pub editing: bool,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter {
+            query: String::new(),
+            editing: true,
+        }
+    }
+
+    // Whether a record matches the query in its artists, title, genres, styles, labels or track
+    // titles, or in its displayed title, so that a query can span its artist and title
+    pub fn matches(&self, record: &Record) -> bool {
+        let query = query_chars(&self.query);
+        let mut fields = record
+            .artists
+            .iter()
+            .chain(record.genre.iter())
+            .chain(record.style.iter())
+            .chain(record.label.iter())
+            .chain(record.tracklist.iter().map(|track| &track.title));
+
+        fuzzy_match(&query, &record.display_title()).is_some()
+            || fuzzy_match(&query, &record.title).is_some()
+            || fields.any(|field| fuzzy_match(&query, field).is_some())
+    }
+
+    // The positions of the characters of a line, such as a record's displayed title, that match
+    // the query, so that they can be highlighted. Returns no positions if the line doesn't match
+    pub fn highlights(&self, line: &str) -> Vec<usize> {
+        fuzzy_match(&query_chars(&self.query), line).unwrap_or_default()
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new()
+    }
+}
+
+// The characters of a query that are matched, lowercased and without whitespace so that e.g.
+// "pink moon" matches "Pink Moon" as well as "Pink Floyd - The Dark Side Of The Moon"
+fn query_chars(query: &str) -> Vec<char> {
+    query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Fuzzy matches a query against some text, returning the positions of the text's characters that
+// matched, or None if it doesn't match
+// The query matches if its characters appear in the text in order, regardless of case. When the
+// query appears whole in the text, that's matched rather than characters scattered across it
+fn fuzzy_match(query: &[char], text: &str) -> Option<Vec<usize>> {
+    // The text is lowercased like the query, where a character can lowercase to several, e.g. 'İ'.
+    // Each keeps the position of the character it came from, which is what's highlighted
+    let (positions, text): (Vec<usize>, Vec<char>) = text
+        .chars()
+        .enumerate()
+        .flat_map(|(i, c)| c.to_lowercase().map(move |lower| (i, lower)))
+        .unzip();
+    if query.is_empty() {
+        return Some(Vec::new());
+    }
+
+    if let Some(start) = text.windows(query.len()).position(|window| window == query) {
+        return Some(dedup(positions[start..start + query.len()].to_vec()));
+    }
+
+    let mut matched = Vec::with_capacity(query.len());
+    let mut query = query.iter().peekable();
+    for (i, c) in text.iter().enumerate() {
+        if query.next_if(|q| *q == c).is_some() {
+            matched.push(positions[i]);
+        }
+    }
+
+    query.peek().is_none().then(|| dedup(matched))
+}
+
+// Removes the repeats of a position, which the characters a character lowercases to share
+fn dedup(mut positions: Vec<usize>) -> Vec<usize> {
+    positions.dedup();
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(query: &str) -> Filter {
+        Filter {
+            query: query.to_string(),
+            editing: false,
+        }
+    }
+
+    #[test]
+    fn matches_across_the_artist_and_title() {
+        let record = Record {
+            title: "The Dark Side Of The Moon".to_string(),
+            artists: vec!["Pink Floyd".to_string()],
+            ..Default::default()
+        };
+
+        assert!(filter("pink moon").matches(&record));
+        assert!(filter("floyd dark").matches(&record));
+        assert!(!filter("moon pink").matches(&record));
+    }
+
+    #[test]
+    fn highlights_whole_matches_over_scattered_ones() {
+        assert_eq!(filter("moon").highlights("Pink Moon"), vec![5, 6, 7, 8]);
+        assert_eq!(filter("pm").highlights("Pink Moon"), vec![0, 5]);
+        assert!(filter("xyz").highlights("Pink Moon").is_empty());
+    }
+
+    #[test]
+    fn matches_characters_that_lowercase_to_several() {
+        // 'İ' lowercases to 'i' and a combining dot, which are both matched and highlighted as
+        // the one character
+        assert_eq!(
+            filter("İstanbul").highlights("İstanbul"),
+            (0..8).collect::<Vec<_>>()
+        );
+        assert_eq!(filter("İs").highlights("Dİs"), vec![1, 2]);
+        assert_eq!(filter("ds").highlights("Dİs"), vec![0, 2]);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::capabilities::{cell_size, ColorSupport, GraphicsProtocol};
+use crate::palette::Palette;
+
+use image::{imageops::FilterType, DynamicImage, RgbImage};
+use std::collections::BTreeSet;
+use std::io::{stdout, Result, Write};
+
+// The ID the cover image is stored under in terminals using the Kitty graphics protocol
+const KITTY_IMAGE_ID: u32 = 1;
+// The largest number of base64 characters the Kitty graphics protocol accepts in one escape code
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+// A cover image prepared to be drawn by the terminal within a panel of cells
+// data: The encoded Sixel image, which is drawn again whenever the panel is printed over
+// offset: The number of cells between the panel's top left corner and the image's, which
+//         centers the image within the panel
+// With Kitty, the image is transmitted to the terminal once when it's prepared, and each draw
+// only places it
+pub struct CoverImage {
+    protocol: GraphicsProtocol,
+    data: Vec<u8>,
+    offset: (u16, u16),
+}
+
+impl CoverImage {
+    // Scales the image to fit within a panel of the given number of cells and prepares it for the
+    // terminal
+    // Returns None for terminals that can't draw images
+    pub fn new(
+        protocol: GraphicsProtocol,
+        image: DynamicImage,
+        cells: (u16, u16),
+    ) -> Result<Option<CoverImage>> {
+        let (cell_w, cell_h) = cell_size();
+        let (panel_w, panel_h) = (
+            cells.0 as u32 * cell_w as u32,
+            cells.1 as u32 * cell_h as u32,
+        );
+        let image = image
+            .resize(panel_w, panel_h, FilterType::Triangle)
+            .into_rgb8();
+        let offset = (
+            ((panel_w - image.width()) / 2 / cell_w as u32) as u16,
+            ((panel_h - image.height()) / 2 / cell_h as u32) as u16,
+        );
+
+        let data = match protocol {
+            GraphicsProtocol::Kitty => {
+                let mut stdout = stdout();
+                stdout.write_all(&kitty_transmit(&image, KITTY_IMAGE_ID))?;
+                stdout.flush()?;
+                Vec::new()
+            }
+            GraphicsProtocol::Sixel => sixel(&image),
+            GraphicsProtocol::Text => return Ok(None),
+        };
+
+        Ok(Some(CoverImage {
+            protocol,
+            data,
+            offset,
+        }))
+    }
+
+    // Draws the image within the panel whose top left corner is at the given position
+    pub fn draw(&self, position: (u16, u16)) -> Result<()> {
+        use crossterm::{cursor, queue};
+
+        let mut stdout = stdout();
+        queue!(
+            stdout,
+            cursor::MoveTo(position.0 + self.offset.0, position.1 + self.offset.1)
+        )?;
+        match self.protocol {
+            GraphicsProtocol::Kitty => stdout.write_all(&kitty_place(KITTY_IMAGE_ID))?,
+            _ => stdout.write_all(&self.data)?,
+        }
+        stdout.flush()
+    }
+}
+
+// Hides the cover image, so that it isn't drawn over overlays such as pickers
+// Only images drawn with Kitty need to be hidden, as text printed over Sixel images replaces them
+pub fn hide_cover(protocol: GraphicsProtocol) -> Result<()> {
+    if protocol == GraphicsProtocol::Kitty {
+        let mut stdout = stdout();
+        stdout.write_all(&kitty_delete(KITTY_IMAGE_ID, false))?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+// Removes the cover image from the terminal's memory when the app quits
+pub fn clear_cover(protocol: GraphicsProtocol) -> Result<()> {
+    if protocol == GraphicsProtocol::Kitty {
+        let mut stdout = stdout();
+        stdout.write_all(&kitty_delete(KITTY_IMAGE_ID, true))?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+// Encodes the Kitty graphics command that stores an image in the terminal under the given ID,
+// without displaying it
+// The image is sent as base64 encoded RGB pixels, split into chunks that each have their own
+// escape code, where m=1 marks that more chunks follow. q=2 stops the terminal from answering,
+// since the answers would be read as key presses
+pub fn kitty_transmit(image: &RgbImage, id: u32) -> Vec<u8> {
+    let encoded = base64::encode(image.as_raw());
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+
+    let mut data = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            data.extend_from_slice(
+                format!(
+                    "\x1b_Ga=t,f=24,s={},v={},i={},q=2,m={};",
+                    image.width(),
+                    image.height(),
+                    id,
+                    more
+                )
+                .as_bytes(),
+            );
+        } else {
+            data.extend_from_slice(format!("\x1b_Gm={};", more).as_bytes());
+        }
+        data.extend_from_slice(chunk);
+        data.extend_from_slice(b"\x1b\\");
+    }
+
+    data
+}
+
+// Encodes the Kitty graphics command that displays a stored image at the cursor
+// Placing the image again with the same placement ID moves it rather than adding another copy, and
+// C=1 leaves the cursor where it is
+pub fn kitty_place(id: u32) -> Vec<u8> {
+    format!("\x1b_Ga=p,i={},p=1,q=2,C=1\x1b\\", id).into_bytes()
+}
+
+// Encodes the Kitty graphics command that removes a stored image's placements, and also frees the
+// image's data when free is true
+pub fn kitty_delete(id: u32, free: bool) -> Vec<u8> {
+    let delete = if free { 'I' } else { 'i' };
+    format!("\x1b_Ga=d,d={},i={},q=2\x1b\\", delete, id).into_bytes()
+}
+
+// Encodes an image as Sixel graphics
+// The image is dithered to the xterm 256 color palette, whose colors are defined as the Sixel
+// color registers of the same index. The image is then drawn in bands of 6 rows of pixels, where
+// each color used in the band is drawn over the band's width as characters whose bits are the
+// pixels of that color in each column
+pub fn sixel(image: &RgbImage) -> Vec<u8> {
+    let palette = match Palette::for_colors(ColorSupport::Palette256) {
+        Some(palette) => palette,
+        None => return Vec::new(),
+    };
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let grid: Vec<Vec<[u8; 3]>> = image
+        .rows()
+        .map(|row| row.map(|pixel| pixel.0).collect())
+        .collect();
+    let indices = palette.dither(&grid);
+
+    // Starts the Sixel image with its size in pixels, where a pixel aspect ratio of 1:1 is set
+    let mut data = format!("\x1bP0;1;0q\"1;1;{};{}", width, height).into_bytes();
+
+    // Defines the colors used in the image, with each channel as a percentage
+    let used: BTreeSet<u8> = indices.iter().flatten().copied().collect();
+    for index in used.iter() {
+        let [r, g, b] = palette.color(*index).map(|c| c as u32 * 100 / 255);
+        data.extend_from_slice(format!("#{};2;{};{};{}", index, r, g, b).as_bytes());
+    }
+
+    for band in indices.chunks(6) {
+        let colors: BTreeSet<u8> = band.iter().flatten().copied().collect();
+        for (i, color) in colors.iter().enumerate() {
+            // Returns to the start of the band to draw the next color over it
+            if i > 0 {
+                data.push(b'$');
+            }
+            data.extend_from_slice(format!("#{}", color).as_bytes());
+
+            let sixels = (0..width).map(|x| {
+                let bits = band
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, row)| row[x] == *color)
+                    .fold(0, |bits, (y, _)| bits | 1 << y);
+                b'?' + bits
+            });
+            push_run_length_encoded(&mut data, sixels);
+        }
+        // Moves to the next band
+        data.push(b'-');
+    }
+
+    data.extend_from_slice(b"\x1b\\");
+    data
+}
+
+// Appends Sixel characters, replacing runs of the same character with "!<count><character>"
+fn push_run_length_encoded(data: &mut Vec<u8>, sixels: impl Iterator<Item = u8>) {
+    let mut push_run = |sixel: u8, count: usize| {
+        if count > 3 {
+            data.extend_from_slice(format!("!{}", count).as_bytes());
+            data.push(sixel);
+        } else {
+            data.extend(std::iter::repeat_n(sixel, count));
+        }
+    };
+
+    let mut run: Option<(u8, usize)> = None;
+    for sixel in sixels {
+        run = match run {
+            Some((last, count)) if last == sixel => Some((last, count + 1)),
+            Some((last, count)) => {
+                push_run(last, count);
+                Some((sixel, 1))
+            }
+            None => Some((sixel, 1)),
+        };
+    }
+    if let Some((last, count)) = run {
+        push_run(last, count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn kitty_transmit_splits_into_chunks() {
+        // 40x40 RGB pixels are 6400 base64 characters, which are sent in two chunks
+        let image = RgbImage::from_pixel(40, 40, Rgb([10, 20, 30]));
+        let data = String::from_utf8(kitty_transmit(&image, 7)).unwrap();
+        let encoded = base64::encode(image.as_raw());
+
+        let first = format!(
+            "\x1b_Ga=t,f=24,s=40,v=40,i=7,q=2,m=1;{}\x1b\\",
+            &encoded[..KITTY_CHUNK_SIZE]
+        );
+        let second = format!("\x1b_Gm=0;{}\x1b\\", &encoded[KITTY_CHUNK_SIZE..]);
+        assert_eq!(data, first + &second);
+    }
+
+    #[test]
+    fn kitty_transmit_sends_small_images_in_one_chunk() {
+        let image = RgbImage::from_pixel(1, 1, Rgb([255, 0, 0]));
+        let data = String::from_utf8(kitty_transmit(&image, 1)).unwrap();
+        assert_eq!(data, "\x1b_Ga=t,f=24,s=1,v=1,i=1,q=2,m=0;/wAA\x1b\\");
+    }
+
+    #[test]
+    fn kitty_places_and_deletes() {
+        assert_eq!(kitty_place(3), b"\x1b_Ga=p,i=3,p=1,q=2,C=1\x1b\\");
+        assert_eq!(kitty_delete(3, false), b"\x1b_Ga=d,d=i,i=3,q=2\x1b\\");
+        assert_eq!(kitty_delete(3, true), b"\x1b_Ga=d,d=I,i=3,q=2\x1b\\");
+    }
+
+    #[test]
+    fn sixel_encodes_bands_of_colors() {
+        // A 6x7 image, black on the left and white on the right of its first 6 rows, with a black
+        // 7th row in a band of its own. Black and white are palette colors 16 and 231
+        let image = RgbImage::from_fn(6, 7, |x, y| {
+            if x < 2 || y == 6 {
+                Rgb([0, 0, 0])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        });
+        let data = String::from_utf8(sixel(&image)).unwrap();
+
+        let expected = [
+            "\x1bP0;1;0q\"1;1;6;7",
+            "#16;2;0;0;0#231;2;100;100;100",
+            "#16~~!4?$#231??!4~-",
+            "#16!6@-",
+            "\x1b\\",
+        ];
+        assert_eq!(data, expected.concat());
+    }
+
+    #[test]
+    fn run_length_encodes_runs_longer_than_3() {
+        let mut data = Vec::new();
+        push_run_length_encoded(&mut data, b"????~~~@@@@@A".iter().copied());
+        assert_eq!(data, b"!4?~~~!5@A");
+    }
+}
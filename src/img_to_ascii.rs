@@ -1,69 +1,417 @@
+use crate::capabilities::ColorSupport;
+use crate::palette::Palette;
+use crate::settings::{parse_name, parse_switch};
+
 use crossterm::queue;
-use image::{DynamicImage, ImageBuffer, Rgb};
-use serde::ser::SerializeTuple;
+use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
 use serde::{Deserialize, Serialize, Serializer};
 
 // A textel is like a pixel but made up of character
 // The are the individual elements comprising 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+// The background color is only set by renderers that use it, such as the half block renderer
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Textel {
-    char: char,
-    color: [u8; 3],
+    pub(crate) char: char,
+    pub(crate) color: [u8; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) background: Option<[u8; 3]>,
+}
+
+// The ways an image can be drawn as textels
+// Block: Each textel is a '█' in the average color of its area of the image
+// HalfBlock: Each textel is a '▀' colored with the top half of its area, over a background colored
+//            with the bottom half, which doubles the art's vertical resolution
+// Ascii: Each textel is a character from BRIGHTNESS_RAMP picked by its area's brightness, so the
+//        art is still recognisable on terminals that can't display its colors
+// Braille: Each textel is a Braille character whose 2x4 dots are raised where the image is brighter
+//          than average, optionally tinted with the average color of its area, which shows the
+//          fine detail of line art and lettering
+// LineArt: Each textel on an edge of the image is a line character, '|', '/', '-', '\' or '_',
+//          following the edge's direction as found with Sobel edge detection. The edges can be
+//          drawn over a dimmed fill of the image's colors, which keeps the shapes of minimalist
+//          and typographic covers that are lost in blocks of color
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderMode {
+    #[default]
+    Block,
+    HalfBlock,
+    Ascii,
+    Braille,
+    LineArt,
 }
 
-// A generic AsciiArt with parameters for its width and height in textels
-// These need to be serialized so that they can be saved with the record collection data, though I
-// should've just saved them as a string of data instead of imiplementing the serde traits
-#[derive(Debug)]
-pub struct AsciiArt<const WIDTH: usize, const HEIGHT: usize> {
-    data: [[Textel; WIDTH]; HEIGHT],
+// The bit of a Braille character that raises the dot at each position of its 2x4 grid, indexed
+// [y][x]. The character is U+2800 plus the bits of its raised dots
+pub(crate) const BRAILLE_DOTS: [[u32; 2]; 4] =
+    [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+// The names of the adjustments made to covers when they're converted to AsciiArt, and how they're
+// set
+pub const ADJUSTMENTS: [&str; 8] = [
+    "linear",
+    "contrast",
+    "brightness",
+    "saturation",
+    "levels",
+    "edges",
+    "fill",
+    "tint",
+];
+pub const ADJUSTMENTS_HELP: &str = "linear on/off, contrast <0+>, brightness <-1 to 1>, \
+    saturation <0+>, levels on/off, edges <0 to 1>, fill <0 to 1>, tint on/off";
+
+// Adjustments made to an image before it's converted to AsciiArt
+// linear_light: Whether colors are averaged in linear light rather than as stored, which keeps
+//               small bright details from being darkened by the dark areas around them
+// contrast: How much the colors are spread apart from middle gray, where 1 leaves them unchanged
+// brightness: How much is added to each color channel, as a fraction of its full range
+// saturation: How colorful the image is made, where 0 is grayscale and 1 leaves it unchanged
+// auto_levels: Whether the image's tones are stretched so that its darkest and brightest parts
+//              use the full range, which brings dark covers out of the shadows
+// edge_threshold: How strong an edge has to be for the line art render mode to draw it, from 0 to
+//                 1, where lower values also draw faint edges
+// edge_fill: How bright the fill of the image's colors behind line art's edges is, from 0 for no
+//            fill to 1 for the image's full colors
+// braille_tint: Whether Braille textels are tinted with the average color of their area, rather
+//               than drawn in white
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Adjustments {
+    pub(crate) linear_light: bool,
+    pub(crate) contrast: f32,
+    pub(crate) brightness: f32,
+    pub(crate) saturation: f32,
+    pub(crate) auto_levels: bool,
+    pub(crate) edge_threshold: f32,
+    pub(crate) edge_fill: f32,
+    pub(crate) braille_tint: bool,
 }
 
-impl<const WIDTH: usize, const HEIGHT: usize> AsciiArt<WIDTH, HEIGHT> {
+impl Default for Adjustments {
+    fn default() -> Self {
+        Adjustments {
+            linear_light: true,
+            contrast: 1.0,
+            brightness: 0.0,
+            saturation: 1.0,
+            auto_levels: false,
+            edge_threshold: 0.15,
+            edge_fill: 0.3,
+            braille_tint: true,
+        }
+    }
+}
+
+impl Adjustments {
+    // Changes one of the adjustments from its name and value as the user writes them, e.g.
+    // "contrast 1.2". Returns None if the name or value isn't valid
+    pub fn set(&mut self, name: &str, value: &str) -> Option<()> {
+        let number = || value.parse::<f32>().ok().filter(|n| n.is_finite());
+        match name.to_lowercase().as_str() {
+            "linear" => self.linear_light = parse_switch(value)?,
+            "contrast" => self.contrast = number().filter(|n| *n >= 0.0)?,
+            "brightness" => self.brightness = number().filter(|n| (-1.0..=1.0).contains(n))?,
+            "saturation" => self.saturation = number().filter(|n| *n >= 0.0)?,
+            "levels" => self.auto_levels = parse_switch(value)?,
+            "edges" => self.edge_threshold = number().filter(|n| (0.0..=1.0).contains(n))?,
+            "fill" => self.edge_fill = number().filter(|n| (0.0..=1.0).contains(n))?,
+            "tint" => self.braille_tint = parse_switch(value)?,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    // Applies the tone and color adjustments to each of the image's pixels
+    fn apply(&self, image: &mut RgbImage) {
+        let (low, high) = if self.auto_levels {
+            luma_levels(image)
+        } else {
+            (0.0, 255.0)
+        };
+        if (low, high) == (0.0, 255.0)
+            && self.contrast == 1.0
+            && self.brightness == 0.0
+            && self.saturation == 1.0
+        {
+            return;
+        }
+
+        for pixel in image.pixels_mut() {
+            let color = pixel.0.map(|c| {
+                let c = (c as f32 - low) * 255.0 / (high - low);
+                (c - 127.5) * self.contrast + 127.5 + self.brightness * 255.0
+            });
+            let luma = 0.299 * color[0] + 0.587 * color[1] + 0.114 * color[2];
+            pixel.0 = color.map(|c| (luma + (c - luma) * self.saturation).clamp(0.0, 255.0) as u8);
+        }
+    }
+}
+
+// The textel blank art is filled with
+const BLANK_TEXTEL: Textel = Textel {
+    char: ' ',
+    color: [0, 0, 0],
+    background: None,
+};
+
+// Terminal cells are about twice as tall as they are wide
+pub(crate) const CELL_ASPECT: f32 = 2.0;
+
+// The characters used by the Ascii render mode, from darkest to brightest
+pub(crate) const BRIGHTNESS_RAMP: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+impl RenderMode {
+    // The names the render modes are written as in the settings
+    pub const NAMES: [(&'static str, RenderMode); 6] = [
+        ("block", RenderMode::Block),
+        ("halfblock", RenderMode::HalfBlock),
+        ("ascii", RenderMode::Ascii),
+        ("braille", RenderMode::Braille),
+        ("lineart", RenderMode::LineArt),
+        ("line", RenderMode::LineArt),
+    ];
+
+    // Parses a render mode's name as it's written in the settings
+    pub fn parse(name: &str) -> Option<RenderMode> {
+        parse_name(name, &RenderMode::NAMES)
+    }
+}
+
+// An AsciiArt of any width and height in textels, so that the same cover can be drawn at whatever
+// size it's displayed at
+// data: The textels row by row
+// These need to be serialized so that they can be saved with the record collection data
+#[derive(Debug, Default)]
+pub struct AsciiArt {
+    width: usize,
+    height: usize,
+    data: Vec<Textel>,
+}
+
+impl AsciiArt {
+    // Returns a blank AsciiArt of the given size, which is used for records without a cover
+    pub fn new(width: usize, height: usize) -> AsciiArt {
+        AsciiArt {
+            width,
+            height,
+            data: vec![BLANK_TEXTEL; width * height],
+        }
+    }
+
     // Converts image to AsciiArt
-    pub fn from_image(image: DynamicImage) -> std::io::Result<AsciiArt<WIDTH, HEIGHT>> {
+    pub fn from_image(
+        image: DynamicImage,
+        width: usize,
+        height: usize,
+    ) -> std::io::Result<AsciiArt> {
+        AsciiArt::from_image_with_mode(
+            image,
+            width,
+            height,
+            RenderMode::Block,
+            &Adjustments::default(),
+        )
+    }
+
+    // Converts image to AsciiArt, drawn with the given render mode after making the adjustments
+    // The image is scaled to fit the art while keeping its aspect ratio, leaving blank textels
+    // around it when its shape doesn't match the art's
+    pub fn from_image_with_mode(
+        image: DynamicImage,
+        width: usize,
+        height: usize,
+        mode: RenderMode,
+        adjustments: &Adjustments,
+    ) -> std::io::Result<AsciiArt> {
         // Converts image to Jpeg like data (i.e. no alpha channel)
-        let image = image.into_rgb8();
-        let (img_w, img_h) = (image.width(), image.height());
-        let pix_tex_ratio = (img_w as usize / WIDTH, img_h as usize / HEIGHT);
+        let mut image = image.into_rgb8();
+        adjustments.apply(&mut image);
+        let linear = adjustments.linear_light;
+
+        let mut art = AsciiArt::new(width, height);
+
+        match mode {
+            RenderMode::Block | RenderMode::Ascii => {
+                let samples = resample(&image, width, height, CELL_ASPECT, linear);
 
-        let mut art = blank_art::<WIDTH, HEIGHT>();
+                for (y, row) in samples.iter().enumerate() {
+                    for (x, sample) in row.iter().enumerate() {
+                        if let Some(color) = *sample {
+                            art.data[y * width + x] = Textel {
+                                char: match mode {
+                                    RenderMode::Ascii => ramp_char(color),
+                                    _ => '█',
+                                },
+                                color,
+                                background: None,
+                            };
+                        }
+                    }
+                }
+            }
+            RenderMode::HalfBlock => {
+                // Each textel is split into two square halves, so the image is sampled at twice
+                // the art's height
+                let samples = resample(&image, width, height * 2, CELL_ASPECT / 2.0, linear);
 
-        // Gets textel by sampling the image data 
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                art.data[y][x] = sample_at(x, y, pix_tex_ratio, &image);
+                for (y, halves) in samples.chunks(2).enumerate() {
+                    for (x, (top, bottom)) in halves[0].iter().zip(&halves[1]).enumerate() {
+                        // Halves outside of the image are left in the terminal's background color
+                        art.data[y * width + x] = match (*top, *bottom) {
+                            (Some(top), bottom) => Textel {
+                                char: '▀',
+                                color: top,
+                                background: bottom,
+                            },
+                            (None, Some(bottom)) => Textel {
+                                char: '▄',
+                                color: bottom,
+                                background: None,
+                            },
+                            (None, None) => continue,
+                        };
+                    }
+                }
+            }
+            RenderMode::Braille => {
+                // Each textel holds a 2x4 grid of dots, which are square
+                let samples = resample(&image, width * 2, height * 4, CELL_ASPECT / 2.0, linear);
+
+                // Dots are raised where the image is brighter than its average brightness
+                let lumas: Vec<u32> = samples
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .map(|c| luma(*c))
+                    .collect();
+                let threshold = lumas.iter().sum::<u32>() / lumas.len().max(1) as u32;
+
+                for (y, rows) in samples.chunks(4).enumerate() {
+                    for x in 0..width {
+                        let mut bits = 0;
+                        let mut sum = [0u32; 3];
+                        let mut count = 0;
+                        for (dot_y, row) in rows.iter().enumerate() {
+                            for (dot_x, sample) in row[x * 2..x * 2 + 2].iter().enumerate() {
+                                let color = match sample {
+                                    Some(color) => *color,
+                                    None => continue,
+                                };
+                                if luma(color) > threshold {
+                                    bits |= BRAILLE_DOTS[dot_y][dot_x];
+                                }
+                                for (sum, channel) in sum.iter_mut().zip(color) {
+                                    *sum += channel as u32;
+                                }
+                                count += 1;
+                            }
+                        }
+
+                        // Textels entirely outside of the image are left blank
+                        if count > 0 {
+                            art.data[y * width + x] = Textel {
+                                char: char::from_u32(0x2800 + bits).unwrap_or(' '),
+                                color: if adjustments.braille_tint {
+                                    sum.map(|channel| (channel / count) as u8)
+                                } else {
+                                    [255, 255, 255]
+                                },
+                                background: None,
+                            };
+                        }
+                    }
+                }
+            }
+            RenderMode::LineArt => {
+                // Each textel is sampled as a 2x4 grid of square areas like Braille, so that the
+                // direction of the edges within it can be told apart
+                let samples = resample(&image, width * 2, height * 4, CELL_ASPECT / 2.0, linear);
+                let gradients = sobel(&samples);
+
+                for (y, (rows, gradient_rows)) in
+                    samples.chunks(4).zip(gradients.chunks(4)).enumerate()
+                {
+                    for x in 0..width {
+                        let mut sum = [0u32; 3];
+                        let mut count = 0;
+                        let mut brightest: Option<[u8; 3]> = None;
+                        // The sums of the gradients' products, whose main axis is the direction
+                        // across the textel's edge, and the gradients' strength in each half of
+                        // the textel
+                        let (mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0);
+                        let (mut upper, mut lower) = (0.0, 0.0);
+                        for (sub_y, (row, gradient_row)) in
+                            rows.iter().zip(gradient_rows).enumerate()
+                        {
+                            let areas = row[x * 2..x * 2 + 2].iter();
+                            for (sample, (gx, gy)) in areas.zip(&gradient_row[x * 2..x * 2 + 2]) {
+                                let color = match sample {
+                                    Some(color) => *color,
+                                    None => continue,
+                                };
+                                for (sum, channel) in sum.iter_mut().zip(color) {
+                                    *sum += channel as u32;
+                                }
+                                count += 1;
+                                if brightest.is_none_or(|brightest| luma(color) > luma(brightest)) {
+                                    brightest = Some(color);
+                                }
+
+                                xx += gx * gx;
+                                yy += gy * gy;
+                                xy += gx * gy;
+                                let strength = (gx * gx + gy * gy).sqrt();
+                                if sub_y < 2 {
+                                    upper += strength;
+                                } else {
+                                    lower += strength;
+                                }
+                            }
+                        }
+
+                        // Textels entirely outside of the image are left blank
+                        let brightest = match brightest {
+                            Some(brightest) => brightest,
+                            None => continue,
+                        };
+                        let color = sum.map(|channel| (channel / count) as u8);
+                        let background = (adjustments.edge_fill > 0.0)
+                            .then(|| color.map(|c| (c as f32 * adjustments.edge_fill) as u8));
+
+                        // The Sobel operator's gradients are up to 4 times the difference in luma
+                        // across an edge, so the strength is scaled back to the range of the luma
+                        let strength = (upper + lower) / (count as f32 * 4.0);
+                        art.data[y * width + x] = if strength > adjustments.edge_threshold {
+                            // Edges are drawn in the color of their brighter side so that they
+                            // stand out from the fill
+                            Textel {
+                                char: edge_char((xx, yy, xy), lower > upper),
+                                color: brightest,
+                                background,
+                            }
+                        } else {
+                            Textel {
+                                char: ' ',
+                                color,
+                                background,
+                            }
+                        };
+                    }
+                }
             }
         }
 
         Ok(art)
     }
 
-    // Prints the AsciiArt like text
-    pub fn print(&self) -> std::io::Result<()> {
+    // Prints the AsciiArt like text, in the colors the terminal can display
+    pub fn print(&self, colors: ColorSupport) -> std::io::Result<()> {
         use std::io::{stdout, Write};
         let mut stdout = stdout();
 
-        use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
-
-        // Setting the background color isn't necessary as we are only printing '█' characters 
-        // which don't show the background
-        queue!(stdout, SetBackgroundColor(Color::White))?;
-        // Prints each individual textel according to their character and color
-        for row in self.data.iter() {
-            for textle in row.iter() {
-                let color = Color::Rgb {
-                    r: textle.color[0],
-                    g: textle.color[1],
-                    b: textle.color[2],
-                };
-                queue!(
-                    stdout,
-                    SetForegroundColor(color),
-                    Print(textle.char.to_string())
-                )?;
-            }
-            queue!(stdout, ResetColor, Print("\n"))?;
+        for row in self.ansi_rows(colors)? {
+            writeln!(stdout, "{}", row)?;
         }
         stdout.flush()?;
 
@@ -72,168 +420,688 @@ impl<const WIDTH: usize, const HEIGHT: usize> AsciiArt<WIDTH, HEIGHT> {
 
     // Prints the AsciiArt at a given terminal position
     // Is essentially the same function as print(), but moving the cursor accordingly
-    pub fn print_at(&self, position: (u16, u16)) -> std::io::Result<()> {
+    pub fn print_at(&self, position: (u16, u16), colors: ColorSupport) -> std::io::Result<()> {
         use std::io::{stdout, Write};
         let mut stdout = stdout();
 
         use crossterm::cursor;
-        use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
-
-        queue!(stdout, SetBackgroundColor(Color::White))?;
-        for (i, row) in self.data.iter().enumerate() {
-            queue!(stdout, cursor::MoveTo(position.0, position.1 + i as u16))?;
-            for textle in row.iter() {
-                let color = Color::Rgb {
-                    r: textle.color[0],
-                    g: textle.color[1],
-                    b: textle.color[2],
-                };
-                queue!(
-                    stdout,
-                    SetForegroundColor(color),
-                    Print(textle.char.to_string())
-                )?;
-            }
-            queue!(stdout, ResetColor)?;
+        use crossterm::style::Print;
+
+        for (i, row) in self.ansi_rows(colors)?.into_iter().enumerate() {
+            queue!(
+                stdout,
+                cursor::MoveTo(position.0, position.1 + i as u16),
+                Print(row)
+            )?;
         }
         stdout.flush()?;
 
         Ok(())
     }
+
+    // The AsciiArt's rows as text, with the escape codes that print each textel in the colors the
+    // terminal can display
+    // Each row ends by resetting the colors, so that it can be printed anywhere, and monochrome
+    // rows are plain text
+    pub fn ansi_rows(&self, colors: ColorSupport) -> std::io::Result<Vec<String>> {
+        use crossterm::style::ResetColor;
+
+        let print_colors = self.print_colors(colors);
+        let mut rows = Vec::with_capacity(self.height);
+        for (row, row_colors) in self.rows().zip(print_colors) {
+            let mut buffer = Vec::new();
+            for (textel, textel_colors) in row.iter().zip(row_colors) {
+                queue_textel(&mut buffer, textel.char, textel_colors)?;
+            }
+            if colors != ColorSupport::Monochrome {
+                queue!(buffer, ResetColor)?;
+            }
+            rows.push(String::from_utf8_lossy(&buffer).into_owned());
+        }
+
+        Ok(rows)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // The art's rows of textels, from top to bottom
+    pub(crate) fn rows(&self) -> std::slice::Chunks<'_, Textel> {
+        // chunks() doesn't accept a size of 0, but art without any width has no textels anyway
+        self.data.chunks(self.width.max(1))
+    }
+
+    // Converts the textels' colors into colors the terminal can display, as a foreground and
+    // background color for each textel
+    // Terminals with a palette get the colors dithered to it. The background colors of half block
+    // textels are dithered as the row below their foreground colors, which is where they were
+    // sampled from the image
+    fn print_colors(&self, colors: ColorSupport) -> Vec<Vec<(PrintColor, PrintColor)>> {
+        let palette = match Palette::for_colors(colors) {
+            Some(palette) => palette,
+            None => {
+                return self
+                    .rows()
+                    .map(|row| {
+                        row.iter()
+                            .map(|textel| match colors {
+                                ColorSupport::Monochrome => {
+                                    (PrintColor::Default, PrintColor::Default)
+                                }
+                                _ => (
+                                    PrintColor::Rgb(textel.color),
+                                    textel
+                                        .background
+                                        .map_or(PrintColor::Default, PrintColor::Rgb),
+                                ),
+                            })
+                            .collect()
+                    })
+                    .collect();
+            }
+        };
+
+        let has_background = |row: &[Textel]| row.iter().any(|t| t.background.is_some());
+        let mut grid = Vec::new();
+        for row in self.rows() {
+            grid.push(row.iter().map(|textel| textel.color).collect());
+            if has_background(row) {
+                grid.push(
+                    row.iter()
+                        .map(|textel| textel.background.unwrap_or(textel.color))
+                        .collect(),
+                );
+            }
+        }
+
+        let indexed = |index: u8| match colors {
+            ColorSupport::Palette16 => PrintColor::Ansi16(index),
+            _ => PrintColor::Ansi256(index),
+        };
+        let mut indices = palette.dither(&grid).into_iter();
+        self.rows()
+            .map(|row| {
+                let foregrounds = indices.next().unwrap_or_default();
+                let backgrounds = if has_background(row) {
+                    indices.next().unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+                row.iter()
+                    .enumerate()
+                    .map(|(x, textel)| {
+                        let background = match (textel.background, backgrounds.get(x)) {
+                            (Some(_), Some(index)) => indexed(*index),
+                            _ => PrintColor::Default,
+                        };
+                        (indexed(foregrounds[x]), background)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// A color as it's printed to the terminal
+// Rgb: A truecolor color
+// Ansi256: An index into the xterm 256 color palette
+// Ansi16: One of the basic ANSI colors
+// Default: The terminal's default foreground or background color
+#[derive(Clone, Copy, Debug)]
+enum PrintColor {
+    Rgb([u8; 3]),
+    Ansi256(u8),
+    Ansi16(u8),
+    Default,
+}
+
+// Queues a textel's character to be printed in its colors
+// Textels in the terminal's default colors, which are all of them on monochrome terminals, are
+// printed without any color codes
+fn queue_textel(
+    stdout: &mut impl std::io::Write,
+    char: char,
+    colors: (PrintColor, PrintColor),
+) -> std::io::Result<()> {
+    use crossterm::style::Print;
+
+    if let (PrintColor::Default, PrintColor::Default) = colors {
+        return queue!(stdout, Print(char.to_string()));
+    }
+
+    queue_color(stdout, colors.0, false)?;
+    queue_color(stdout, colors.1, true)?;
+    queue!(stdout, Print(char.to_string()))
 }
 
-// Samples the image for a textel at a given positon, taking the average of 9 color samples
-fn sample_at(
-    tex_x: usize,
-    tex_y: usize,
-    pix_tex_ratio: (usize, usize),
+// Queues setting the foreground or background color
+fn queue_color(
+    stdout: &mut impl std::io::Write,
+    color: PrintColor,
+    background: bool,
+) -> std::io::Result<()> {
+    use crossterm::style::{Color, Print, SetBackgroundColor, SetForegroundColor};
+
+    let color = match color {
+        PrintColor::Rgb([r, g, b]) => Color::Rgb { r, g, b },
+        PrintColor::Ansi256(index) => Color::AnsiValue(index),
+        PrintColor::Default => Color::Reset,
+        // Crossterm writes the basic colors as 256 color palette indices, which basic terminals
+        // don't understand, so their original codes are written instead, e.g. 31 for red text
+        PrintColor::Ansi16(index) => {
+            let base = match (background, index < 8) {
+                (false, true) => 30,
+                (false, false) => 90 - 8,
+                (true, true) => 40,
+                (true, false) => 100 - 8,
+            };
+            return queue!(stdout, Print(format!("\x1b[{}m", base + index as u16)));
+        }
+    };
+
+    if background {
+        queue!(stdout, SetBackgroundColor(color))
+    } else {
+        queue!(stdout, SetForegroundColor(color))
+    }
+}
+
+// Resamples an image into a grid of colors, each of which is the average color of the area of the
+// image it covers
+// cell_aspect: How many times taller than they are wide the grid's cells are displayed, so that the
+//              image keeps its proportions in the terminal
+// linear: Whether the colors are averaged in linear light
+// The image is scaled to fit within the grid and centered, and cells that are mostly outside of
+// it are None
+fn resample(
     image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
-) -> Textel {
-    // The top-left corner of the rectangle being sampled for the given textel
-    let base = (tex_x * pix_tex_ratio.0, tex_y * pix_tex_ratio.1);
-    // The offsets of the sample points for the textel
-    // This is pretty much just a 3x3 grid
-    let offsets: [[(usize, usize); 3]; 3] = [
-        [
-            (0, 0),
-            ((pix_tex_ratio.0 - 1) / 2, 0),
-            (pix_tex_ratio.0 - 1, 0),
-        ],
-        [
-            (0, ((pix_tex_ratio.1 - 1) / 2)),
-            ((pix_tex_ratio.0 - 1) / 2, ((pix_tex_ratio.1 - 1) / 2)),
-            (pix_tex_ratio.0 - 1, ((pix_tex_ratio.1 - 1) / 2)),
-        ],
-        [
-            (0, pix_tex_ratio.1 - 1),
-            ((pix_tex_ratio.0 - 1) / 2, pix_tex_ratio.1 - 1),
-            (pix_tex_ratio.0 - 1, pix_tex_ratio.1 - 1),
-        ],
-    ];
+    width: usize,
+    height: usize,
+    cell_aspect: f32,
+    linear: bool,
+) -> Vec<Vec<Option<[u8; 3]>>> {
+    // Image files store colors gamma encoded (sRGB), so they're decoded to the light they
+    // represent before averaging and encoded again afterwards
+    let decode = |c: f32| if linear { srgb_to_linear(c) } else { c };
+    let encode = |c: f32| if linear { linear_to_srgb(c) } else { c };
+    let lookup: Vec<f32> = (0..=255).map(|c| decode(c as f32)).collect();
 
-    // Retrieves the colors at the sample positions described just above
-    let samples = offsets
-        .map(|ls| ls.map(|(dx, dy)| image.get_pixel((base.0 + dx) as u32, (base.1 + dy) as u32)));
+    let (img_w, img_h) = (image.width() as f32, image.height() as f32);
+    if img_w == 0.0 || img_h == 0.0 {
+        return vec![vec![None; width]; height];
+    }
+
+    // The grid's size measured in cell widths, and the number of image pixels per cell width
+    let (grid_w, grid_h) = (width as f32, height as f32 * cell_aspect);
+    let scale = (img_w / grid_w).max(img_h / grid_h);
+    // Where the image starts within the grid, in pixels, so that it's centered
+    let origin = (
+        (img_w - grid_w * scale) / 2.0,
+        (img_h - grid_h * scale) / 2.0,
+    );
+    let (cell_w, cell_h) = (scale, scale * cell_aspect);
+
+    (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let left = origin.0 + x as f32 * cell_w;
+                    let top = origin.1 + y as f32 * cell_h;
+                    area_average(image, &lookup, (left, top), (left + cell_w, top + cell_h))
+                        .map(|color| color.map(|c| encode(c).round().clamp(0.0, 255.0) as u8))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Finds the horizontal and vertical gradients of the samples' luma, from 0 to 1, with the Sobel
+// operator, which weighs the differences between each sample's neighbours on either side
+// Samples outside of the image count as the sample in the middle, so that the image's own border
+// isn't drawn as an edge
+fn sobel(samples: &[Vec<Option<[u8; 3]>>]) -> Vec<Vec<(f32, f32)>> {
+    let lumas: Vec<Vec<Option<f32>>> = samples
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|sample| sample.map(|color| luma(color) as f32 / 255.0))
+                .collect()
+        })
+        .collect();
+
+    lumas
+        .iter()
+        .enumerate()
+        .map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(x, center)| {
+                    let center = center.unwrap_or(0.0);
+                    let at = |dx: isize, dy: isize| {
+                        let row = lumas.get(y.checked_add_signed(dy)?)?;
+                        *row.get(x.checked_add_signed(dx)?)?
+                    };
+                    let at = |dx, dy| at(dx, dy).unwrap_or(center);
+
+                    let gx = at(1, -1) + 2.0 * at(1, 0) + at(1, 1)
+                        - at(-1, -1)
+                        - 2.0 * at(-1, 0)
+                        - at(-1, 1);
+                    let gy = at(-1, 1) + 2.0 * at(0, 1) + at(1, 1)
+                        - at(-1, -1)
+                        - 2.0 * at(0, -1)
+                        - at(1, -1);
+                    (gx, gy)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Picks the line character that follows an edge, from the sums of its gradients' products
+// The gradients point across the edge, so their main angle, which is half the angle of the
+// products' sums, is turned a quarter to find the edge's direction. Flat edges in the bottom half
+// of their textel are drawn as '_'
+fn edge_char((xx, yy, xy): (f32, f32, f32), lower: bool) -> char {
+    // The image's y axis points down, so a gradient at 45 degrees crosses an edge rising to the
+    // right
+    let angle = (0.5 * (2.0 * xy).atan2(xx - yy))
+        .to_degrees()
+        .rem_euclid(180.0);
+    match angle {
+        a if !(22.5..157.5).contains(&a) => '|',
+        a if a < 67.5 => '/',
+        a if a < 112.5 && lower => '_',
+        a if a < 112.5 => '-',
+        _ => '\\',
+    }
+}
 
-    // Sums the samples' color
-    let mut sum: [u32; 3] = [0, 0, 0];
-    for row in samples.iter() {
-        for color in row.iter() {
-            sum[0] += color[0] as u32;
-            sum[1] += color[1] as u32;
-            sum[2] += color[2] as u32;
+// Averages the colors of the pixels within a rectangle of the image, weighting each pixel by how
+// much of it is within the rectangle
+// Each channel's value is looked up in values, e.g. to decode it to linear light, before averaging
+// Returns None if less than half of the rectangle is within the image
+fn area_average(
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    values: &[f32],
+    top_left: (f32, f32),
+    bottom_right: (f32, f32),
+) -> Option<[f32; 3]> {
+    let (img_w, img_h) = (image.width() as f32, image.height() as f32);
+    let area = (bottom_right.0 - top_left.0) * (bottom_right.1 - top_left.1);
+
+    // Clips the rectangle to the image
+    let (x0, y0) = (top_left.0.max(0.0), top_left.1.max(0.0));
+    let (x1, y1) = (bottom_right.0.min(img_w), bottom_right.1.min(img_h));
+    if x1 <= x0 || y1 <= y0 || (x1 - x0) * (y1 - y0) < area / 2.0 {
+        return None;
+    }
+
+    let mut sum = [0.0f32; 3];
+    let mut total_weight = 0.0;
+    for py in y0.floor() as u32..y1.ceil() as u32 {
+        // How much of the pixel's height is within the rectangle
+        let weight_y = (y1.min(py as f32 + 1.0) - y0.max(py as f32)).max(0.0);
+        for px in x0.floor() as u32..x1.ceil() as u32 {
+            let weight = weight_y * (x1.min(px as f32 + 1.0) - x0.max(px as f32)).max(0.0);
+            let pixel = image.get_pixel(px, py);
+            for (sum, channel) in sum.iter_mut().zip(pixel.0) {
+                *sum += values[channel as usize] * weight;
+            }
+            total_weight += weight;
         }
     }
-    // To then take the average of the 9 samples
-    let color = sum.map(|x| (x / 9) as u8);
 
-    Textel { char: '█', color }
+    Some(sum.map(|channel| channel / total_weight))
+}
+
+// Converts an sRGB channel value from 0 to 255 into linear light, from 0 to 1
+fn srgb_to_linear(value: f32) -> f32 {
+    let value = value / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Converts linear light from 0 to 1 into an sRGB channel value from 0 to 255
+fn linear_to_srgb(value: f32) -> f32 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    value * 255.0
 }
 
-// A utility function that returns a blank AsciiArt struct 
-pub fn blank_art<const WIDTH: usize, const HEIGHT: usize>() -> AsciiArt<WIDTH, HEIGHT> {
-    AsciiArt {
-        data: [[Textel {
-            char: ' ',
-            color: [0, 0, 0],
-        }; WIDTH]; HEIGHT],
+// Finds the darkest and brightest tones of an image, ignoring the darkest and brightest 1% of its
+// pixels so that a few stray pixels don't stop the levels from being stretched
+fn luma_levels(image: &RgbImage) -> (f32, f32) {
+    let mut histogram = [0usize; 256];
+    for pixel in image.pixels() {
+        histogram[luma(pixel.0) as usize] += 1;
+    }
+
+    let clip = image.pixels().len() / 100;
+    let level_at = |levels: Vec<usize>| {
+        let mut count = 0;
+        for level in levels {
+            count += histogram[level];
+            if count > clip {
+                return level;
+            }
+        }
+        0
+    };
+    let low = level_at((0..256).collect());
+    let high = level_at((0..256).rev().collect());
+
+    // Images with a single tone are left as they are
+    if high > low {
+        (low as f32, high as f32)
+    } else {
+        (0.0, 255.0)
     }
 }
 
+// Picks the character from the brightness ramp matching a color's perceived brightness
+fn ramp_char(color: [u8; 3]) -> char {
+    BRIGHTNESS_RAMP[luma(color) as usize * (BRIGHTNESS_RAMP.len() - 1) / 255]
+}
+
+// The luma of a color, its brightness with each channel weighted by how bright it appears, from 0
+// to 255
+fn luma(color: [u8; 3]) -> u32 {
+    let [r, g, b] = color.map(|c| c as u32);
+    (299 * r + 587 * g + 114 * b) / 1000
+}
+
 // The following code is needed to serialize the AsciiArt so that it can be serialized along with
-// the rest of the record data. This is not a great implementation and should be changed!
+// the rest of the record data.
+// AsciiArt is saved compactly as its size, its characters, and its colors packed into a base64
+// string of RGB bytes. Art saved in the older format, a list of rows of textel objects, still loads
 
-// A wrapper struct needed since we can't impl a base type [Textel; WIDTH]
-// I used the crate "serde_arrays" to automatically implement the Serialize and Deserialize traits
-// for a row of Textels. This crate should in principle also work for 2D arrays such as AsciiArt's
-// data, but for some reason it wouldn't work for me so I ended up implementing manually.
+// The compact form AsciiArt is serialized as
+// chars: The textels' characters row by row, or a single character if all the textels share it
+// colors: The textels' colors as base64 encoded RGB bytes, row by row
+// backgrounds: The background colors of the textels that have one in the same form, if any do
+// background_mask: Which textels have a background, as base64 encoded bits with one per textel
+//                  from the first byte's lowest bit on. It's left out when every textel has one,
+//                  which is also how art saved before it was added is read
 #[derive(Serialize, Deserialize)]
-pub struct RowWrapper<const WIDTH: usize> {
-    #[serde(with = "serde_arrays")]
-    row: [Textel; WIDTH],
+struct CompactArt {
+    width: usize,
+    height: usize,
+    chars: String,
+    colors: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backgrounds: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    background_mask: Option<String>,
 }
 
-// Implements Serialize trait for AsciiArt. This is made easy since "serde_array" handles
-// serializing each row.
-impl<const WIDTH: usize, const HEIGHT: usize> Serialize for AsciiArt<WIDTH, HEIGHT> {
+impl Serialize for AsciiArt {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut s = serializer.serialize_tuple(HEIGHT)?;
-        for row in self.data {
-            let wrapper = RowWrapper { row };
-            s.serialize_element(&wrapper)?;
+        let textels = self.data.iter();
+
+        let mut chars: String = textels.clone().map(|textel| textel.char).collect();
+        if let Some(first) = chars.chars().next() {
+            if chars.chars().all(|c| c == first) {
+                chars = first.to_string();
+            }
+        }
+        let colors: Vec<u8> = textels.clone().flat_map(|textel| textel.color).collect();
+        let backgrounds: Vec<u8> = textels
+            .clone()
+            .flat_map(|textel| textel.background)
+            .flatten()
+            .collect();
+
+        // Art where only some textels have a background, e.g. half blocks letterboxed on one
+        // side, marks which ones do
+        let with_background = textels.filter(|textel| textel.background.is_some()).count();
+        let background_mask =
+            (with_background > 0 && with_background < self.data.len()).then(|| {
+                let mut mask = vec![0u8; self.data.len().div_ceil(8)];
+                for (i, textel) in self.data.iter().enumerate() {
+                    if textel.background.is_some() {
+                        mask[i / 8] |= 1 << (i % 8);
+                    }
+                }
+                base64::encode(mask)
+            });
+
+        CompactArt {
+            width: self.width,
+            height: self.height,
+            chars,
+            colors: base64::encode(colors),
+            backgrounds: (with_background > 0).then(|| base64::encode(backgrounds)),
+            background_mask,
         }
-        s.end()
+        .serialize(serializer)
     }
 }
 
 // The code to implement the Deserialize trait for AsciiArt
-use serde::de::{SeqAccess, Visitor};
+use serde::de::{Error, MapAccess, SeqAccess, Visitor};
 use std::fmt;
 
-struct AsciiArtVisitor<const WIDTH: usize, const HEIGHT: usize> {}
-impl<const WIDTH: usize, const HEIGHT: usize> AsciiArtVisitor<WIDTH, HEIGHT> {
+// A wrapper struct needed to deserialize the rows of the older format, where each row was saved
+// as an object
+#[derive(Deserialize)]
+struct RowWrapper {
+    row: Vec<Textel>,
+}
+
+struct AsciiArtVisitor {}
+impl AsciiArtVisitor {
     fn new() -> Self {
         AsciiArtVisitor {}
     }
 }
 
-impl<'de, const WIDTH: usize, const HEIGHT: usize> Visitor<'de> for AsciiArtVisitor<WIDTH, HEIGHT> {
-    type Value = AsciiArt<WIDTH, HEIGHT>;
+impl<'de> Visitor<'de> for AsciiArtVisitor {
+    type Value = AsciiArt;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         formatter.write_str("a AsciiArt struct")
     }
 
+    // Deserializes the compact format
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let compact = CompactArt::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+        let colors = base64::decode(&compact.colors).map_err(A::Error::custom)?;
+        let backgrounds = match &compact.backgrounds {
+            Some(backgrounds) => base64::decode(backgrounds).map_err(A::Error::custom)?,
+            None => Vec::new(),
+        };
+        let size = compact.width * compact.height;
+
+        // Which textels have a background, which is every textel when there's no mask
+        let has_background: Vec<bool> = match &compact.background_mask {
+            Some(mask) => {
+                let mask = base64::decode(mask).map_err(A::Error::custom)?;
+                (0..size)
+                    .map(|i| mask.get(i / 8).is_some_and(|bits| bits & 1 << (i % 8) != 0))
+                    .collect()
+            }
+            None => vec![compact.backgrounds.is_some(); size],
+        };
+
+        let chars: Vec<char> = compact.chars.chars().collect();
+        if colors.len() != size * 3
+            || (chars.len() != 1 && chars.len() != size)
+            || backgrounds.len() != has_background.iter().filter(|has| **has).count() * 3
+        {
+            return Err(A::Error::custom("AsciiArt data doesn't match its size"));
+        }
+
+        let mut backgrounds = backgrounds.chunks(3);
+        let data = (0..size)
+            .map(|i| Textel {
+                char: if chars.len() == 1 { chars[0] } else { chars[i] },
+                color: [colors[i * 3], colors[i * 3 + 1], colors[i * 3 + 2]],
+                background: match has_background[i] {
+                    true => backgrounds.next().map(|b| [b[0], b[1], b[2]]),
+                    false => None,
+                },
+            })
+            .collect();
+
+        Ok(AsciiArt {
+            width: compact.width,
+            height: compact.height,
+            data,
+        })
+    }
+
+    // Deserializes the older format, a list of rows of textels, which were all the same width
     fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
     where
         A: SeqAccess<'de>,
     {
-        let mut art = blank_art::<WIDTH, HEIGHT>();
-        let mut i: usize = 0;
-        loop {
-            match seq.next_element::<RowWrapper<WIDTH>>()? {
-                Some(row) => {
-                    art.data[i] = row.row;
-                    i += 1;
-                }
-                None => break,
+        let mut art = AsciiArt::default();
+        while let Some(mut row) = seq.next_element::<RowWrapper>()? {
+            if art.height == 0 {
+                art.width = row.row.len();
             }
+            if row.row.len() != art.width {
+                return Err(A::Error::custom("AsciiArt rows aren't the same width"));
+            }
+            art.data.append(&mut row.row);
+            art.height += 1;
         }
 
         Ok(art)
     }
 }
 
-impl<'de, const WIDTH: usize, const HEIGHT: usize> Deserialize<'de> for AsciiArt<WIDTH, HEIGHT> {
+impl<'de> Deserialize<'de> for AsciiArt {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_seq(AsciiArtVisitor::new())
+        deserializer.deserialize_any(AsciiArtVisitor::new())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::COVER_ART_SIZE;
+
+    const MODES: [RenderMode; 5] = [
+        RenderMode::Block,
+        RenderMode::HalfBlock,
+        RenderMode::Ascii,
+        RenderMode::Braille,
+        RenderMode::LineArt,
+    ];
+
+    // Covers smaller than the art used to underflow while resampling
+    #[test]
+    fn converts_tiny_covers_in_every_mode() {
+        for (width, height) in [(1, 1), (3, 2)] {
+            let image =
+                RgbImage::from_fn(width, height, |x, y| Rgb([x as u8 * 80, y as u8 * 120, 50]));
+            for mode in MODES {
+                let art = AsciiArt::from_image_with_mode(
+                    DynamicImage::ImageRgb8(image.clone()),
+                    COVER_ART_SIZE.0,
+                    COVER_ART_SIZE.1,
+                    mode,
+                    &Adjustments::default(),
+                )
+                .unwrap();
+                assert_eq!((art.width(), art.height()), COVER_ART_SIZE);
+            }
+        }
+    }
+
+    // The sums of a single gradient's products, as edge_char is given them
+    fn products((gx, gy): (f32, f32)) -> (f32, f32, f32) {
+        (gx * gx, gy * gy, gx * gy)
+    }
+
+    #[test]
+    fn picks_edge_chars_across_gradients() {
+        // The gradients point across the edges, e.g. a gradient pointing right crosses an edge
+        // going up and down
+        assert_eq!(edge_char(products((1.0, 0.0)), false), '|');
+        assert_eq!(edge_char(products((1.0, 1.0)), false), '/');
+        assert_eq!(edge_char(products((0.0, 1.0)), false), '-');
+        assert_eq!(edge_char(products((-1.0, 1.0)), false), '\\');
+
+        // Gradients pointing the opposite way cross the same edges
+        assert_eq!(edge_char(products((-1.0, 0.0)), false), '|');
+        assert_eq!(edge_char(products((-1.0, -1.0)), false), '/');
+
+        // Flat edges in the bottom half of their textel sit on its baseline
+        assert_eq!(edge_char(products((0.0, 1.0)), true), '_');
+        assert_eq!(edge_char(products((1.0, 0.0)), true), '|');
+    }
+
+    #[test]
+    fn sobel_finds_the_gradients_of_a_step() {
+        // A 4x3 image, black on its left half and white on its right
+        let black = Some([0, 0, 0]);
+        let white = Some([255, 255, 255]);
+        let samples = vec![vec![black, black, white, white]; 3];
+        let gradients = sobel(&samples);
+
+        // The step is found on both sides of it, and the image's own border isn't an edge
+        assert_eq!(
+            gradients[1],
+            vec![(0.0, 0.0), (4.0, 0.0), (4.0, 0.0), (0.0, 0.0)]
+        );
+        assert_eq!(edge_char(products(gradients[1][1]), false), '|');
+
+        // Samples outside of the image count as the one in the middle
+        let samples = vec![vec![white, None, white]];
+        assert_eq!(sobel(&samples)[0], vec![(0.0, 0.0); 3]);
+    }
 
+    #[test]
+    fn round_trips_mixed_backgrounds() {
+        let round_trip = |art: &AsciiArt| {
+            let json = serde_json::to_string(art).unwrap();
+            let loaded: AsciiArt = serde_json::from_str(&json).unwrap();
+            assert_eq!((loaded.width, loaded.height), (art.width, art.height));
+            assert_eq!(loaded.data, art.data);
+        };
+
+        // Half blocks with a background beside letterboxed ones without one
+        let mut art = AsciiArt::new(3, 3);
+        for (i, textel) in art.data.iter_mut().enumerate() {
+            *textel = Textel {
+                char: '▀',
+                color: [i as u8, 10, 20],
+                background: (i % 2 == 0).then_some([30, i as u8, 40]),
+            };
+        }
+        round_trip(&art);
+
+        // Every textel with a background, and none with one
+        art.data
+            .iter_mut()
+            .for_each(|textel| textel.background = Some([1, 2, 3]));
+        round_trip(&art);
+        art.data
+            .iter_mut()
+            .for_each(|textel| textel.background = None);
+        round_trip(&art);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::app::max_len;
+
+use crossterm::style::Stylize;
+use crossterm::terminal;
+use std::io::Result;
+
+// The smallest terminal the app can be drawn in
+pub const MIN_COLS: u16 = 60;
+pub const MIN_ROWS: u16 = 20;
+// The number of rows taken by the header and the footer boxes
+const HEADER_ROWS: u16 = 3;
+const FOOTER_ROWS: u16 = 3;
+// The bounds of the records list's width, which otherwise takes a third of the terminal
+const LIST_MIN_COLS: u16 = 30;
+const LIST_MAX_COLS: u16 = 50;
+// The width of the record's details and tracklist within the info pane
+const DETAILS_COLS: u16 = 33;
+// The narrowest the info pane can be while fitting the details between its borders
+const INFO_MIN_COLS: u16 = DETAILS_COLS + 6;
+// The fewest rows a cover is drawn in, below which it's hidden
+const COVER_MIN_ROWS: u16 = 8;
+
+// The characters a box's borders are drawn with, as its left corner, its line and its right corner
+pub const TOP: (char, char, char) = ('╔', '═', '╗');
+pub const BOTTOM: (char, char, char) = ('╚', '═', '╝');
+pub const DIVIDER: (char, char, char) = ('╟', '─', '╢');
+
+// A rectangle of terminal cells
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    // The column just right of the rectangle
+    pub fn right(&self) -> u16 {
+        self.x + self.width
+    }
+
+    // The row just below the rectangle
+    pub fn bottom(&self) -> u16 {
+        self.y + self.height
+    }
+}
+
+// Where each part of the app is drawn, worked out from the terminal's size so that the app fills
+// whatever terminal it's run in
+// header, footer: The boxes at the top and bottom of the terminal, spanning its width
+// content: The space between the header and footer, which the panes and pickers are drawn in
+// list: The pane listing the records, including its borders
+// info: The pane with the selected record's information including its borders, or None when the
+//       terminal is too narrow for it, in which case the list spans the whole width
+// details: Where the record's details and tracklist are printed within the info pane
+// cover: Where the record's cover is drawn within the info pane, or None when there's no room
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub cols: u16,
+    pub rows: u16,
+    pub header: Rect,
+    pub content: Rect,
+    pub footer: Rect,
+    pub list: Rect,
+    pub info: Option<Rect>,
+    pub details: Option<Rect>,
+    pub cover: Option<Rect>,
+}
+
+impl Layout {
+    // The layout of the terminal at its current size
+    pub fn current() -> Result<Layout> {
+        let (cols, rows) = terminal::size()?;
+        Ok(Layout::new(cols, rows))
+    }
+
+    // Lays the app out within a terminal of the given size
+    // The list takes a third of the width and the info pane the rest, with the details on its left
+    // and the cover as large as fits on its right. On smaller terminals the cover is hidden first,
+    // then the info pane
+    pub fn new(cols: u16, rows: u16) -> Layout {
+        let header = Rect {
+            x: 0,
+            y: 0,
+            width: cols,
+            height: HEADER_ROWS,
+        };
+        let content = Rect {
+            x: 0,
+            y: HEADER_ROWS,
+            width: cols,
+            height: rows.saturating_sub(HEADER_ROWS + FOOTER_ROWS),
+        };
+        let footer = Rect {
+            x: 0,
+            y: content.bottom(),
+            width: cols,
+            height: FOOTER_ROWS,
+        };
+
+        // The panes are separated by a column
+        let list_cols = (cols / 3).clamp(LIST_MIN_COLS, LIST_MAX_COLS);
+        let info_cols = cols.saturating_sub(list_cols + 1);
+        if info_cols < INFO_MIN_COLS {
+            return Layout {
+                cols,
+                rows,
+                header,
+                content,
+                footer,
+                list: content,
+                info: None,
+                details: None,
+                cover: None,
+            };
+        }
+
+        let list = Rect {
+            width: list_cols,
+            ..content
+        };
+        let info = Rect {
+            x: list.right() + 1,
+            width: info_cols,
+            ..content
+        };
+
+        // The details start below the pane's title, with a margin from its border. The pane's
+        // body ends above its bottom border
+        let details = Rect {
+            x: info.x + 4,
+            y: info.y + 4,
+            width: DETAILS_COLS,
+            height: info.bottom().saturating_sub(info.y + 5),
+        };
+
+        // Covers are square, so they're twice as many cells wide as tall, and they're centered in
+        // the space right of the details
+        let cover_left = details.right() + 3;
+        let cover_space = (
+            info.right().saturating_sub(cover_left + 3),
+            details.height.saturating_sub(2),
+        );
+        let cover_rows = cover_space.1.min(cover_space.0 / 2);
+        let cover = (cover_rows >= COVER_MIN_ROWS).then(|| Rect {
+            x: cover_left + (cover_space.0 - cover_rows * 2) / 2,
+            y: details.y,
+            width: cover_rows * 2,
+            height: cover_rows,
+        });
+
+        Layout {
+            cols,
+            rows,
+            header,
+            content,
+            footer,
+            list,
+            info: Some(info),
+            details: Some(details),
+            cover,
+        }
+    }
+
+    // Whether the terminal is large enough to draw the app in
+    pub fn fits(&self) -> bool {
+        self.cols >= MIN_COLS && self.rows >= MIN_ROWS
+    }
+
+    // The bottom borders of the panes, which close them off when a box is drawn below them
+    pub fn panes_bottom(&self) -> String {
+        match self.info {
+            Some(info) => format!(
+                "{} {}",
+                border(self.list.width, BOTTOM),
+                border(info.width, BOTTOM)
+            ),
+            None => border(self.list.width, BOTTOM),
+        }
+    }
+}
+
+// A horizontal border of a box of the given width, e.g. "╔════╗"
+pub fn border(width: u16, (left, line, right): (char, char, char)) -> String {
+    let line: String = std::iter::repeat_n(line, width.saturating_sub(2) as usize).collect();
+    format!("{}{}{}", left, line, right)
+}
+
+// A row of a box of the given width holding text, which is padded or truncated to fit between the
+// box's borders
+pub fn boxed(text: &str, width: u16) -> String {
+    let inner = width.saturating_sub(4) as usize;
+    format!("║ {:inner$} ║", max_len(&mut text.to_string(), inner))
+}
+
+// A row of a box of the given width with its text centered between the box's borders
+pub fn boxed_centered(text: &str, width: u16) -> String {
+    let inner = width.saturating_sub(4) as usize;
+    format!("║ {:^inner$} ║", max_len(&mut text.to_string(), inner))
+}
+
+// A row of a box of the given width like boxed, with the characters of the text at the given
+// positions highlighted, such as those matching the records list's filter
+pub fn boxed_highlighted(text: &str, highlights: &[usize], width: u16) -> String {
+    let inner = width.saturating_sub(4) as usize;
+    let mut fitted = text.to_string();
+    max_len(&mut fitted, inner);
+    // The characters cut off by truncation aren't highlighted in the "..." replacing them
+    let kept = if text.chars().count() > inner {
+        inner.saturating_sub(3)
+    } else {
+        inner
+    };
+
+    let mut row = String::from("║ ");
+    for (i, c) in fitted.chars().enumerate() {
+        if i < kept && highlights.contains(&i) {
+            row.push_str(&c.bold().underlined().to_string());
+        } else {
+            row.push(c);
+        }
+    }
+    row.push_str(&" ".repeat(inner.saturating_sub(fitted.chars().count())));
+    row.push_str(" ║");
+
+    row
+}
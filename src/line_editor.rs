@@ -0,0 +1,380 @@
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::{cursor, execute, queue};
+use std::io::{stdout, Result};
+
+// Where the lines entered in the editor are saved between runs of the app
+const HISTORY_PATH: &str = "data/command_history.json";
+// The number of lines kept in the history, after which the oldest are dropped
+const MAX_HISTORY: usize = 100;
+// The furthest a misspelled word can be from a suggestion, in edits
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+// A line editor that reads a line in raw mode within a row of the app, such as the command prompt
+// in the footer
+// history: The lines entered before, oldest first
+pub struct LineEditor {
+    history: Vec<String>,
+}
+
+// The line being edited
+// chars: The line's characters
+// cursor: The index of the character the cursor is before
+// scroll: The index of the first character shown, when the line is wider than its space
+// browsing: The index of the history's line being shown, or None when the user's own line is shown
+// draft: The user's own line, kept while the history is browsed
+// completion: The start of the word being completed, its matches and the index of the one
+//             shown, while Tab is pressed repeatedly to cycle through them
+struct Line {
+    chars: Vec<char>,
+    cursor: usize,
+    scroll: usize,
+    browsing: Option<usize>,
+    draft: Vec<char>,
+    completion: Option<(usize, Vec<String>, usize)>,
+}
+
+impl LineEditor {
+    // Loads the history saved by earlier runs of the app, or starts an empty one if there isn't
+    // one or it can't be read, since the history isn't worth stopping the app over
+    pub fn load() -> LineEditor {
+        let history = std::fs::read_to_string(HISTORY_PATH)
+            .ok()
+            .and_then(|data_string| serde_json::from_str(data_string.as_str()).ok())
+            .unwrap_or_default();
+
+        LineEditor { history }
+    }
+
+    // Reads a line typed in the given number of columns from a position of the terminal, returning
+    // None if the user cancels it with Esc or the terminal is resized
+    // Left and Right move the cursor, by words with Ctrl, Home and End or Ctrl-A and Ctrl-E move it
+    // to either end, Ctrl-W erases the word before the cursor and Ctrl-U the whole line before it.
+    // Up and Down browse the history, and Tab completes the word before the cursor from the words
+    // complete returns for the words before it, where pressing it again cycles through the matches
+    pub fn read_line(
+        &mut self,
+        position: (u16, u16),
+        width: u16,
+        complete: impl Fn(&[&str]) -> Vec<String>,
+    ) -> Result<Option<String>> {
+        let mut line = Line {
+            chars: Vec::new(),
+            cursor: 0,
+            scroll: 0,
+            browsing: None,
+            draft: Vec::new(),
+            completion: None,
+        };
+
+        loop {
+            line.print(position, width as usize)?;
+            let (code, modifiers) = match read()? {
+                Event::Key(KeyEvent { code, modifiers }) => (code, modifiers),
+                Event::Resize(..) => return Ok(None),
+                _ => continue,
+            };
+            let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+
+            // Any other key than Tab ends the cycling through the completions
+            if code != KeyCode::Tab {
+                line.completion = None;
+            }
+
+            match code {
+                KeyCode::Enter => break,
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Tab => line.complete(&complete),
+                KeyCode::Left if ctrl => line.cursor = line.word_start(),
+                KeyCode::Right if ctrl => line.cursor = line.word_end(),
+                KeyCode::Left => line.cursor = line.cursor.saturating_sub(1),
+                KeyCode::Right => line.cursor = (line.cursor + 1).min(line.chars.len()),
+                KeyCode::Home => line.cursor = 0,
+                KeyCode::End => line.cursor = line.chars.len(),
+                KeyCode::Char('a') if ctrl => line.cursor = 0,
+                KeyCode::Char('e') if ctrl => line.cursor = line.chars.len(),
+                KeyCode::Char('w') if ctrl => line.erase(line.word_start()..line.cursor),
+                KeyCode::Char('u') if ctrl => line.erase(0..line.cursor),
+                KeyCode::Char('k') if ctrl => line.erase(line.cursor..line.chars.len()),
+                KeyCode::Char(_) if ctrl => (),
+                KeyCode::Char(c) => {
+                    line.chars.insert(line.cursor, c);
+                    line.cursor += 1;
+                }
+                KeyCode::Backspace if line.cursor > 0 => line.erase(line.cursor - 1..line.cursor),
+                KeyCode::Delete if line.cursor < line.chars.len() => {
+                    line.erase(line.cursor..line.cursor + 1)
+                }
+                KeyCode::Up => line.browse(&self.history, true),
+                KeyCode::Down => line.browse(&self.history, false),
+                _ => (),
+            }
+        }
+
+        let entered: String = line.chars.iter().collect();
+        let entered = entered.trim().to_string();
+        self.add_to_history(&entered);
+
+        Ok(Some(entered))
+    }
+
+    // Adds a line to the end of the history and saves it, unless it's blank or repeats the last one
+    // A history that can't be saved is still kept for the rest of the run, rather than failing the
+    // line that was entered
+    fn add_to_history(&mut self, entered: &str) {
+        if entered.is_empty() || self.history.last().is_some_and(|last| last == entered) {
+            return;
+        }
+
+        self.history.push(entered.to_string());
+        let excess = self.history.len().saturating_sub(MAX_HISTORY);
+        self.history.drain(..excess);
+
+        let _ = self.save_history();
+    }
+
+    // Writes the history to its data file, creating the data directory if it doesn't exist yet
+    fn save_history(&self) -> Result<()> {
+        if let Some(directory) = std::path::Path::new(HISTORY_PATH).parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let data_string = serde_json::to_string_pretty(&self.history)?;
+        std::fs::write(HISTORY_PATH, data_string)
+    }
+}
+
+impl Line {
+    // Prints the part of the line that fits in the given width, scrolled to keep the cursor
+    // visible, and moves the terminal's cursor to the line's
+    fn print(&mut self, position: (u16, u16), width: usize) -> Result<()> {
+        let width = width.max(1);
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + width {
+            self.scroll = self.cursor + 1 - width;
+        }
+
+        let shown: String = self.chars.iter().skip(self.scroll).take(width).collect();
+        let mut stdout = stdout();
+        queue!(
+            stdout,
+            cursor::MoveTo(position.0, position.1),
+            Print(format!("{:width$}", shown))
+        )?;
+        execute!(
+            stdout,
+            cursor::MoveTo(position.0 + (self.cursor - self.scroll) as u16, position.1),
+            cursor::Show
+        )
+    }
+
+    // Removes a range of the line's characters, leaving the cursor where they were
+    fn erase(&mut self, range: std::ops::Range<usize>) {
+        self.cursor = range.start;
+        self.chars.drain(range);
+    }
+
+    // The index of the start of the word before the cursor, skipping the spaces before it
+    fn word_start(&self) -> usize {
+        let before = &self.chars[..self.cursor];
+        let end = before.iter().rposition(|c| *c != ' ').map_or(0, |i| i + 1);
+        before[..end]
+            .iter()
+            .rposition(|c| *c == ' ')
+            .map_or(0, |i| i + 1)
+    }
+
+    // The index of the end of the word after the cursor, skipping the spaces before it
+    fn word_end(&self) -> usize {
+        let after = &self.chars[self.cursor..];
+        let start = after.iter().position(|c| *c != ' ').unwrap_or(after.len());
+        let end = after[start..]
+            .iter()
+            .position(|c| *c == ' ')
+            .map_or(after.len(), |i| start + i);
+        self.cursor + end
+    }
+
+    // Shows the line before or after the one shown in the history, keeping the user's own line so
+    // that it's shown again after the history's last line
+    fn browse(&mut self, history: &[String], back: bool) {
+        let browsing = match (self.browsing, back) {
+            (None, true) if !history.is_empty() => Some(history.len() - 1),
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < history.len() => Some(i + 1),
+            (Some(_), false) => None,
+            (None, _) => return,
+        };
+
+        if self.browsing.is_none() {
+            self.draft = self.chars.clone();
+        }
+        self.chars = match browsing {
+            Some(i) => history[i].chars().collect(),
+            None => self.draft.clone(),
+        };
+        self.browsing = browsing;
+        self.cursor = self.chars.len();
+    }
+
+    // Completes the word before the cursor with the words that can follow the ones before it
+    // A single match is completed with a space after it, and several are completed as far as they
+    // share a beginning. Once the word can't be completed any further, pressing Tab again
+    // replaces it with each of the matches in turn
+    fn complete(&mut self, complete: &impl Fn(&[&str]) -> Vec<String>) {
+        // Cycles to the next match when Tab is pressed again
+        if let Some((start, matches, index)) = &mut self.completion {
+            *index = (*index + 1) % matches.len();
+            let replacement: Vec<char> = matches[*index].chars().collect();
+            let start = *start;
+            self.erase(start..self.cursor);
+            self.insert(&replacement);
+            return;
+        }
+
+        let start = self.chars[..self.cursor]
+            .iter()
+            .rposition(|c| *c == ' ')
+            .map_or(0, |i| i + 1);
+        let before: String = self.chars[..start].iter().collect();
+        let word: String = self.chars[start..self.cursor].iter().collect();
+        let words: Vec<&str> = before.split_whitespace().collect();
+
+        let matches: Vec<String> = complete(&words)
+            .into_iter()
+            .filter(|candidate| candidate.to_lowercase().starts_with(&word.to_lowercase()))
+            .collect();
+
+        match matches.len() {
+            0 => (),
+            1 => {
+                self.erase(start..self.cursor);
+                self.insert(&format!("{} ", matches[0]).chars().collect::<Vec<char>>());
+            }
+            _ => {
+                let prefix = common_prefix(&matches);
+                if prefix.chars().count() > word.chars().count() {
+                    self.erase(start..self.cursor);
+                    self.insert(&prefix.chars().collect::<Vec<char>>());
+                } else {
+                    // The word is replaced with the first match, and the next Tab cycles on
+                    self.erase(start..self.cursor);
+                    self.insert(&matches[0].chars().collect::<Vec<char>>());
+                    self.completion = Some((start, matches, 0));
+                }
+            }
+        }
+    }
+
+    // Inserts characters at the cursor, moving the cursor past them
+    fn insert(&mut self, chars: &[char]) {
+        self.chars
+            .splice(self.cursor..self.cursor, chars.iter().copied());
+        self.cursor += chars.len();
+    }
+}
+
+// The longest beginning that all the words share, ignoring case, in the case of the first word
+fn common_prefix(words: &[String]) -> String {
+    let first: Vec<char> = words[0].chars().collect();
+    let mut len = first.len();
+    for word in words[1..].iter() {
+        len = word
+            .chars()
+            .zip(first.iter())
+            .take(len)
+            .take_while(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+            .count();
+    }
+
+    first[..len].iter().collect()
+}
+
+// The words a misspelled word was likely meant to be, closest first: the ones it differs from
+// only by case, the ones it begins, and the ones a couple of typos away from it
+pub fn suggestions<'a>(word: &str, words: &[&'a str]) -> Vec<&'a str> {
+    let word = word.to_lowercase();
+    let mut suggestions: Vec<(usize, &str)> = words
+        .iter()
+        .map(|candidate| {
+            let lowercase = candidate.to_lowercase();
+            let distance = if !word.is_empty() && lowercase.starts_with(&word) {
+                0
+            } else {
+                edit_distance(&word, &lowercase)
+            };
+            (distance, *candidate)
+        })
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .collect();
+    suggestions.sort_by_key(|(distance, _)| *distance);
+
+    suggestions.into_iter().map(|(_, word)| word).collect()
+}
+
+// The number of characters that have to be inserted, removed or replaced to turn one word into
+// another, i.e. their Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a_char != *b_char);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("sync", "sync"), 0);
+        assert_eq!(edit_distance("", "sync"), 4);
+        assert_eq!(edit_distance("synk", "sync"), 1);
+        assert_eq!(edit_distance("snyc", "sync"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn suggests_close_words_closest_first() {
+        let words = ["Add", "AddRelease", "Adjust", "Restore", "Remove"];
+
+        // Words differing only by case, and words begun by the typed word, come first
+        assert_eq!(suggestions("add", &words), vec!["Add", "AddRelease"]);
+        assert_eq!(suggestions("Restor", &words), vec!["Restore"]);
+        // Typos are matched up to MAX_SUGGESTION_DISTANCE edits away
+        assert_eq!(suggestions("Remvoe", &words), vec!["Remove"]);
+        assert_eq!(suggestions("Restove", &words), vec!["Restore", "Remove"]);
+        assert!(suggestions("Export", &words).is_empty());
+    }
+
+    #[test]
+    fn finds_common_prefixes_ignoring_case() {
+        let words = |words: &[&str]| {
+            words
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(common_prefix(&words(&["AddRelease", "Add"])), "Add");
+        assert_eq!(
+            common_prefix(&words(&["halfblock", "HALFBLOCK"])),
+            "halfblock"
+        );
+        assert_eq!(
+            common_prefix(&words(&["Restore", "Regenerate", "Remove"])),
+            "Re"
+        );
+        assert_eq!(common_prefix(&words(&["sync", "sort"])), "s");
+        assert_eq!(common_prefix(&words(&["sync", "color"])), "");
+    }
+}
@@ -1,14 +1,32 @@
 pub mod app;
+pub mod capabilities;
+pub mod cli;
 pub mod config;
 pub mod discogs_client;
+pub mod export;
+pub mod filter;
+pub mod graphics;
 pub mod img_to_ascii;
+pub mod layout;
+pub mod line_editor;
+pub mod palette;
+pub mod picker;
 pub mod record;
+pub mod settings;
+pub mod sort;
+pub mod storage;
 
 use std::io::Result;
 
 use app::App;
 
 fn main() -> Result<()> {
+    // "vinylla ascii ..." converts an image without starting the app
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|command| command == "ascii") {
+        return cli::run_ascii(&args[1..]);
+    }
+
     let mut app = App::init()?;
     app.run()?;
     app.quit()?;
@@ -0,0 +1,182 @@
+use crate::capabilities::ColorSupport;
+
+// The colors of the xterm 256 color palette's 6x6x6 color cube, at indices 16 to 231, are made
+// from these levels of each channel
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// The basic ANSI colors as xterm displays them by default, in index order
+// Terminals' themes change these, so they are only an approximation of what is displayed
+const ANSI_16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+// A fixed set of colors the terminal can display, which RGB colors are quantized to
+// colors: The palette's colors
+// offset: The terminal's palette index of the first color
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+    offset: u8,
+}
+
+impl Palette {
+    // The palette used with the terminal's color support, or None if it can display any color
+    // or none at all
+    pub fn for_colors(colors: ColorSupport) -> Option<Palette> {
+        match colors {
+            ColorSupport::Palette256 => Some(Palette::xterm_256()),
+            ColorSupport::Palette16 => Some(Palette {
+                colors: ANSI_16.to_vec(),
+                offset: 0,
+            }),
+            ColorSupport::TrueColor | ColorSupport::Monochrome => None,
+        }
+    }
+
+    // The xterm 256 color palette, without its first 16 colors since they're the basic ANSI
+    // colors which terminals' themes change
+    fn xterm_256() -> Palette {
+        let mut colors = Vec::with_capacity(240);
+        for r in CUBE_LEVELS {
+            for g in CUBE_LEVELS {
+                for b in CUBE_LEVELS {
+                    colors.push([r, g, b]);
+                }
+            }
+        }
+        // The grayscale ramp at indices 232 to 255
+        for i in 0..24 {
+            let level = 8 + i * 10;
+            colors.push([level, level, level]);
+        }
+
+        Palette { colors, offset: 16 }
+    }
+
+    // Quantizes a grid of colors to the palette's indices, with Floyd-Steinberg dithering
+    // Each color's quantization error is spread to the neighbours that haven't been quantized yet,
+    // so that areas of the grid keep their average color
+    pub fn dither(&self, grid: &[Vec<[u8; 3]>]) -> Vec<Vec<u8>> {
+        let mut colors: Vec<Vec<[f32; 3]>> = grid
+            .iter()
+            .map(|row| row.iter().map(|color| color.map(|c| c as f32)).collect())
+            .collect();
+        let mut indices = vec![Vec::new(); grid.len()];
+
+        for y in 0..colors.len() {
+            for x in 0..colors[y].len() {
+                let color = colors[y][x].map(|c| c.clamp(0.0, 255.0));
+                let nearest = self.nearest(color);
+                indices[y].push(nearest as u8 + self.offset);
+
+                let error: [f32; 3] =
+                    std::array::from_fn(|c| color[c] - self.colors[nearest][c] as f32);
+                let mut diffuse = |x: Option<usize>, y: usize, weight: f32| {
+                    if let Some(color) = x.and_then(|x| colors.get_mut(y)?.get_mut(x)) {
+                        for (channel, error) in color.iter_mut().zip(error) {
+                            *channel += error * weight;
+                        }
+                    }
+                };
+                diffuse(Some(x + 1), y, 7.0 / 16.0);
+                diffuse(x.checked_sub(1), y + 1, 3.0 / 16.0);
+                diffuse(Some(x), y + 1, 5.0 / 16.0);
+                diffuse(Some(x + 1), y + 1, 1.0 / 16.0);
+            }
+        }
+
+        indices
+    }
+
+    // The color at one of the palette's indices, as returned by dither()
+    pub fn color(&self, index: u8) -> [u8; 3] {
+        self.colors[(index - self.offset) as usize]
+    }
+
+    // Finds the index in the palette's colors of the color closest to the given one
+    // Channels are weighted by how sensitive the eye is to them
+    fn nearest(&self, color: [f32; 3]) -> usize {
+        let distance = |other: &[u8; 3]| {
+            let d: [f32; 3] = std::array::from_fn(|c| color[c] - other[c] as f32);
+            2.0 * d[0] * d[0] + 4.0 * d[1] * d[1] + 3.0 * d[2] * d[2]
+        };
+
+        (0..self.colors.len())
+            .min_by(|a, b| distance(&self.colors[*a]).total_cmp(&distance(&self.colors[*b])))
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_exact_palette_colors() {
+        let palette = Palette::xterm_256();
+        let nearest = |color: [u8; 3]| palette.nearest(color.map(|c| c as f32)) as u8 + 16;
+
+        // The color cube's corners
+        assert_eq!(nearest([0, 0, 0]), 16);
+        assert_eq!(nearest([0, 0, 255]), 21);
+        assert_eq!(nearest([255, 0, 0]), 196);
+        assert_eq!(nearest([255, 255, 255]), 231);
+        // The grayscale ramp's ends, and a cube color inside it
+        assert_eq!(nearest([8, 8, 8]), 232);
+        assert_eq!(nearest([238, 238, 238]), 255);
+        assert_eq!(nearest([95, 135, 175]), 67);
+
+        let ansi = Palette::for_colors(ColorSupport::Palette16).unwrap();
+        for (i, color) in ANSI_16.iter().enumerate() {
+            assert_eq!(ansi.nearest(color.map(|c| c as f32)), i);
+        }
+    }
+
+    #[test]
+    fn dither_keeps_flat_palette_colors() {
+        let palette = Palette::xterm_256();
+        let grid = vec![vec![[95, 135, 175]; 6]; 4];
+        assert_eq!(palette.dither(&grid), vec![vec![67; 6]; 4]);
+    }
+
+    #[test]
+    fn dither_spreads_the_error_of_a_gradient() {
+        // A gray halfway between two of the palette's grays is drawn as a mix of both, which
+        // averages out to it, rather than rounded to either
+        let palette = Palette::xterm_256();
+        let grid = vec![vec![[13, 13, 13]; 8]; 8];
+        let indices: Vec<u8> = palette.dither(&grid).concat();
+        assert!(indices.contains(&232) && indices.contains(&233));
+        let average = indices
+            .iter()
+            .map(|index| palette.color(*index)[0] as f32)
+            .sum::<f32>()
+            / indices.len() as f32;
+        assert!((average - 13.0).abs() < 1.0, "{}", average);
+
+        // Each step of a gradient finer than the palette's is mixed from the colors around it
+        let gradient: Vec<Vec<[u8; 3]>> = vec![
+            (0..16)
+                .map(|x| [100 + x * 2, 100 + x * 2, 100 + x * 2])
+                .collect();
+            4
+        ];
+        let dithered = palette.dither(&gradient);
+        let distinct: std::collections::BTreeSet<u8> = dithered.concat().into_iter().collect();
+        assert!(distinct.len() >= 3, "{:?}", distinct);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::app::max_len;
+use crate::layout::{border, boxed, boxed_centered, Layout, BOTTOM, DIVIDER, TOP};
+
+use crossterm::style::{Print, Stylize};
+use crossterm::{cursor, execute, queue, terminal};
+use std::io::{stdout, Result, Write};
+
+// The number of rows of the picker that aren't list rows: its borders, title, column headers,
+// dividers and hint line
+const FRAME_ROWS: u16 = 8;
+
+// A scrollable overlay listing rows of columns, from which the user picks one with the arrow keys
+// columns: The header and width of each column
+// rows: The values displayed in each column of every row
+// selected: The index of the currently highlighted row
+// offset: The index of the first row shown in the list
+// hint: The description of the Enter and Esc keys shown below the list
+pub struct Picker {
+    title: String,
+    hint: String,
+    columns: Vec<(&'static str, usize)>,
+    rows: Vec<Vec<String>>,
+    selected: usize,
+    offset: usize,
+}
+
+impl Picker {
+    pub fn new(title: &str, columns: Vec<(&'static str, usize)>, rows: Vec<Vec<String>>) -> Picker {
+        Picker {
+            title: title.to_string(),
+            hint: "Enter: Select   Esc: Back".to_string(),
+            columns,
+            rows,
+            selected: 0,
+            offset: 0,
+        }
+    }
+
+    // Replaces the default description of what Enter and Esc do
+    pub fn with_hint(mut self, hint: &str) -> Picker {
+        self.hint = hint.to_string();
+        self
+    }
+
+    // Runs the picker until the user selects a row with Enter, returning its index, or cancels
+    // with Esc, returning None
+    pub fn run(&mut self) -> Result<Option<usize>> {
+        use crossterm::event::{read, Event::Key, Event::Resize, KeyCode, KeyEvent};
+
+        if self.rows.is_empty() {
+            return Ok(None);
+        }
+
+        loop {
+            self.print()?;
+            let visible_rows = list_rows(&Layout::current()?);
+            match read()? {
+                Key(KeyEvent { code, .. }) => match code {
+                    KeyCode::Enter | KeyCode::Right => return Ok(Some(self.selected)),
+                    KeyCode::Esc | KeyCode::Left => return Ok(None),
+                    KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                    KeyCode::Down => self.selected = (self.selected + 1).min(self.rows.len() - 1),
+                    KeyCode::PageUp => self.selected = self.selected.saturating_sub(visible_rows),
+                    KeyCode::PageDown => {
+                        self.selected = (self.selected + visible_rows).min(self.rows.len() - 1)
+                    }
+                    KeyCode::Home => self.selected = 0,
+                    KeyCode::End => self.selected = self.rows.len() - 1,
+                    _ => (),
+                },
+                // The app behind the picker is drawn again once the picker closes
+                Resize(..) => execute!(stdout(), terminal::Clear(terminal::ClearType::All))?,
+                _ => (),
+            }
+
+            // Scrolls the list so that the selected row is always visible
+            let visible_rows = list_rows(&Layout::current()?);
+            if self.selected < self.offset {
+                self.offset = self.selected;
+            } else if self.selected >= self.offset + visible_rows {
+                self.offset = self.selected + 1 - visible_rows;
+            }
+        }
+    }
+
+    // Prints the picker over the app's content section
+    fn print(&self) -> Result<()> {
+        let mut stdout = stdout();
+        let content = Layout::current()?.content;
+        let width = content.width;
+        let mut row = content.y;
+        let mut print_row = |line: String| {
+            let result = queue!(stdout, cursor::MoveTo(content.x, row), Print(line));
+            row += 1;
+            result
+        };
+
+        print_row(border(width, TOP))?;
+        print_row(boxed_centered(&self.title, width))?;
+        print_row(border(width, DIVIDER))?;
+
+        // Prints the column headers
+        let headers: Vec<String> = self.columns.iter().map(|(h, _)| h.to_string()).collect();
+        print_row(format!("║ {} ║", self.format_row(&headers, width)))?;
+        print_row(border(width, DIVIDER))?;
+
+        // Prints the visible rows, highlighting the selected one
+        let visible_rows = list_rows(&Layout::current()?);
+        for i in self.offset..(self.offset + visible_rows) {
+            match self.rows.get(i) {
+                Some(row) if i == self.selected => {
+                    print_row(format!("║ {} ║", self.format_row(row, width).reverse()))?
+                }
+                Some(row) => print_row(format!("║ {} ║", self.format_row(row, width)))?,
+                None => print_row(boxed("", width))?,
+            }
+        }
+
+        let hint = format!(
+            "{}/{}   ↑/↓: Move   PgUp/PgDn: Scroll   {}",
+            self.selected + 1,
+            self.rows.len(),
+            self.hint
+        );
+        print_row(border(width, DIVIDER))?;
+        print_row(boxed(&hint, width))?;
+        print_row(border(width, BOTTOM))?;
+
+        stdout.flush()?;
+        execute!(stdout, cursor::Hide)?;
+
+        Ok(())
+    }
+
+    // Pads or truncates each value of a row to its column's width, filling the space between the
+    // borders of a picker of the given width
+    fn format_row(&self, row: &[String], width: u16) -> String {
+        let inner = width.saturating_sub(4) as usize;
+        let mut line = self
+            .columns
+            .iter()
+            .zip(row.iter())
+            .map(|((_, width), value)| {
+                let mut value = value.clone();
+                format!("{:width$}", max_len(&mut value, *width), width = *width)
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        max_len(&mut line, inner);
+
+        format!("{:inner$}", line)
+    }
+}
+
+// The number of list rows that fit in the picker between its headers and its hint line
+fn list_rows(layout: &Layout) -> usize {
+    layout.content.height.saturating_sub(FRAME_ROWS).max(1) as usize
+}
@@ -1,16 +1,25 @@
-use crate::img_to_ascii::AsciiArt;
+use crate::discogs_client::CollectionInstance;
+use crate::img_to_ascii::{Adjustments, AsciiArt, RenderMode};
+use crate::storage::timestamp;
 
+use image::DynamicImage;
 use reqwest::blocking::get;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::io::Result;
 use std::path::Path;
+use uuid::Uuid;
 
-const COLLECTION_PATH: &str = "data/collection.json";
+// Where the records' original cover images are cached
+const COVERS_PATH: &str = "data/covers";
+// The size in textels of the cover art saved with each record
+pub const COVER_ART_SIZE: (usize, usize) = (45, 20);
 
 // A struct containing a track's data
-#[derive(Serialize, Deserialize, Debug)]
+// Fields missing from the saved data, e.g. ones added in a newer version, are left blank
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct Track {
     pub(crate) title: String,
     pub(crate) duration: String,
@@ -18,118 +27,227 @@ pub struct Track {
 }
 
 // A struct containing a record's data
-#[derive(Serialize, Deserialize, Debug)]
+// Each record has its own local ID so that the collection can hold several copies of the same
+// album. Records saved before they had IDs are given a new one when they're loaded, and like
+// tracks, any other missing fields are left blank
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct Record {
+    #[serde(default = "Uuid::new_v4")]
+    pub(crate) id: Uuid,
     pub(crate) title: String,
     pub(crate) artists: Vec<String>,
     pub(crate) year: u16,
     pub(crate) genre: Vec<String>,
     pub(crate) style: Vec<String>,
+    pub(crate) label: Vec<String>,
     pub(crate) country: String,
     pub(crate) format: String,
-    pub(crate) image: AsciiArt<45, 20>,
+    pub(crate) image: AsciiArt,
     pub(crate) tracklist: Vec<Track>,
+    // The record's Discogs release ID, used to sync the record with the user's Discogs collection
+    pub(crate) release_id: Option<u64>,
+    // The record's copy in the user's Discogs collection, if it has been synced
+    pub(crate) instance: Option<CollectionInstance>,
+    // The file name of the record's original cover image in the covers cache, so that its
+    // AsciiArt can be rendered again at any size
+    pub(crate) cover: Option<String>,
+    // The adjustments made to the record's cover when it's converted to AsciiArt, replacing the
+    // ones in the settings
+    pub(crate) adjustments: Option<Adjustments>,
+    // The Unix time the record was added to the collection. Records added before this was saved
+    // have the time their collection file was last saved before it was, or 0 if that's unknown
+    pub(crate) added: u64,
 }
 
-// A RecordCollection is indexed on a pair of strings containing the first artist of the album, and
-// the title of the album
-pub type RecordCollection = HashMap<(String, String), Record>;
-
-// Loads the user's collection from the serialized data file into a RecordCollection object
-pub fn load_collection() -> Result<RecordCollection> {
-    let mut collection: HashMap<(String, String), Record> = HashMap::new();
-    if Path::new(COLLECTION_PATH).exists() {
-        let data_string = std::fs::read_to_string(COLLECTION_PATH)?;
-        let v: Vec<Record> = serde_json::from_str(data_string.as_str())?;
-        for record in v {
-            collection.insert((record.artists[0].clone(), record.title.clone()), record);
-        }
-    }
-
-    Ok(collection)
-}
+// A RecordCollection is indexed on the records' local IDs
+pub type RecordCollection = HashMap<Uuid, Record>;
 
 impl Record {
-    // Returns a record from the json data returned by the Discogs API
-    pub fn from_discogs(record_data: Value) -> Result<Record> {
+    // Returns a record from the json data returned by the Discogs API, with its cover art converted
+    // with the given adjustments
+    // Missing information is left blank rather than failing, since not every release on Discogs
+    // lists its year, formats or images
+    pub fn from_discogs(record_data: Value, adjustments: &Adjustments) -> Result<Record> {
         // Takes the names of the artists data list and adds them to the records artists Vec
-        let artists: Vec<String> = record_data["artists"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|a| process_artist(a["name"].clone()))
-            .collect();
-
-        let genre: Vec<String> = match record_data["genres"].as_array() {
-            Some(vec) => vec
-                .iter()
-                .map(|v| v.as_str().unwrap().to_string())
-                .collect(),
+        let mut artists: Vec<String> = match record_data["artists"].as_array() {
+            Some(artists) => artists.iter().map(|a| process_artist(&a["name"])).collect(),
             None => Vec::new(),
         };
+        // Records are displayed and sorted by their first artist, so there must be at least one
+        if artists.is_empty() {
+            artists.push("Unknown Artist".to_string());
+        }
+
+        let genre = string_list(&record_data["genres"]);
+        let style = string_list(&record_data["styles"]);
 
-        let style: Vec<String> = match record_data["styles"].as_array() {
-            Some(vec) => vec
+        let label = label_names(&record_data);
+
+        // Takes the first format from the Discogs data, and formats it to a string
+        let format = &record_data["formats"][0];
+        let descriptions = string_list(&format["descriptions"]).join(", ");
+        let format_str = match format["name"].as_str() {
+            Some(name) if descriptions.is_empty() => name.to_string(),
+            Some(name) => format!("{}: {}", name, descriptions),
+            None => descriptions,
+        };
+
+        // Headings (e.g. "Side A" or a suite's name) are listed as tracks but aren't played
+        let tracklist = match record_data["tracklist"].as_array() {
+            Some(tracks) => tracks
                 .iter()
-                .map(|v| v.as_str().unwrap().to_string())
+                .filter(|track| track["type_"].as_str() != Some("heading"))
+                .map(|track| Track {
+                    title: track["title"].as_str().unwrap_or_default().to_string(),
+                    duration: track["duration"].as_str().unwrap_or_default().to_string(),
+                    position: track["position"].as_str().unwrap_or_default().to_string(),
+                })
                 .collect(),
             None => Vec::new(),
         };
 
-        // Takes the first format from the Discogs data, and formats it to a string
-        let format = &record_data["formats"].as_array().unwrap()[0];
-        let format_str = format!(
-            "{}: {}",
-            format["name"].as_str().unwrap(),
-            format["descriptions"]
-                .as_array()
-                .unwrap()
-                .iter()
-                .map(|d| d.as_str().unwrap())
-                .collect::<Vec<&str>>()
-                .join(", ")
-        );
-
-        // Retrieves the album cover image url from the record's json data...
-        let img_url = record_data["images"].as_array().unwrap()[0]["resource_url"]
-            .as_str()
-            .unwrap();
-        // ... uses it to send a GET request to retrieve the image bytes
-        let img_bytes = get(img_url).unwrap().bytes().unwrap();
-        // and loads it as an image to later be converted into AsciiArt
-        let image = image::load_from_memory(&img_bytes).unwrap();
-
-        let tracklist = record_data["tracklist"]
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|track| Track {
-                title: track["title"].as_str().unwrap().to_string(),
-                duration: track["duration"].as_str().unwrap().to_string(),
-                position: track["position"].as_str().unwrap().to_string(),
-            })
-            .collect();
-
-        Ok(Record {
-            title: record_data["title"].as_str().unwrap().to_string(),
+        let mut record = Record {
+            id: Uuid::new_v4(),
+            title: record_data["title"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
             artists,
-            year: record_data["year"].as_u64().unwrap() as u16,
+            year: record_data["year"].as_u64().unwrap_or(0) as u16,
             genre,
             style,
-            country: record_data["country"].as_str().unwrap().to_string(),
+            label,
+            country: record_data["country"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
             format: format_str,
-            image: AsciiArt::<45, 20>::from_image(image)?,
+            image: AsciiArt::new(COVER_ART_SIZE.0, COVER_ART_SIZE.1),
             tracklist,
-        })
+            release_id: record_data["id"].as_u64(),
+            instance: None,
+            cover: None,
+            adjustments: None,
+            added: timestamp(),
+        };
+
+        // Downloads the album cover into the covers cache, and converts it into AsciiArt
+        // Releases without a cover keep a blank image
+        if let Some(image) = cover_url(&record_data).and_then(|url| record.fetch_cover(url)) {
+            record.image = AsciiArt::from_image_with_mode(
+                image,
+                COVER_ART_SIZE.0,
+                COVER_ART_SIZE.1,
+                RenderMode::Block,
+                adjustments,
+            )?;
+        }
+
+        Ok(record)
+    }
+
+    // Downloads a cover image and saves it to the covers cache as the record's cover, returning
+    // the decoded image. Returns None if the image couldn't be downloaded, decoded or saved
+    pub fn fetch_cover(&mut self, url: &str) -> Option<DynamicImage> {
+        let img_bytes = get(url).ok()?.bytes().ok()?;
+        let image = image::load_from_memory(&img_bytes).ok()?;
+
+        // The cached cover is named after the record's ID, keeping the image's original format
+        let extension = image::guess_format(&img_bytes)
+            .ok()
+            .and_then(|format| format.extensions_str().first().copied())
+            .unwrap_or("img");
+        let file_name = format!("{}.{}", self.id, extension);
+        std::fs::create_dir_all(COVERS_PATH).ok()?;
+        std::fs::write(Path::new(COVERS_PATH).join(&file_name), &img_bytes).ok()?;
+        self.cover = Some(file_name);
+
+        Some(image)
+    }
+
+    // Loads the record's original cover image from the covers cache
+    pub fn load_cover(&self) -> Option<DynamicImage> {
+        let img_bytes = std::fs::read(Path::new(COVERS_PATH).join(self.cover.as_ref()?)).ok()?;
+        image::load_from_memory(&img_bytes).ok()
+    }
+
+    // Deletes the record's original cover image from the covers cache, e.g. once the record is
+    // removed from the collection. A cover that's already gone is ignored
+    pub fn delete_cover(&self) {
+        if let Some(file_name) = &self.cover {
+            let _ = std::fs::remove_file(Path::new(COVERS_PATH).join(file_name));
+        }
+    }
+
+    // The adjustments made to the record's cover, which are its own if it has any and the given
+    // global ones otherwise
+    pub fn adjustments(&self, global: &Adjustments) -> Adjustments {
+        self.adjustments.unwrap_or(*global)
+    }
+}
+
+impl Record {
+    // The record's first artist, which it is listed under
+    pub fn artist(&self) -> &str {
+        self.artists
+            .first()
+            .map_or("Unknown Artist", |a| a.as_str())
+    }
+
+    // The record's first artist and title as they are displayed in the app
+    pub fn display_title(&self) -> String {
+        format!("{} - {}", self.artist(), self.title)
     }
 }
 
-// This removes any "(X)" from the artist name that discogs appends when there
+// Retrieves the url of the album cover image from a release's json data
+pub fn cover_url(record_data: &Value) -> Option<&str> {
+    record_data["images"][0]["resource_url"].as_str()
+}
+
+// Collects a json list of strings, such as a release's genres, into a Vec
+fn string_list(value: &Value) -> Vec<String> {
+    match value.as_array() {
+        Some(values) => values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|v| v.to_string())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// The names of a release's labels from the json data returned by the Discogs API
+// Labels are listed once for each of their catalog numbers, and have the same " (X)" suffixes as
+// artists
+pub(crate) fn label_names(record_data: &Value) -> Vec<String> {
+    let mut label: Vec<String> = Vec::new();
+    let names = record_data["labels"].as_array().into_iter().flatten();
+    for name in names
+        .map(|label| &label["name"])
+        .filter(|name| name.is_string())
+    {
+        let name = process_artist(name);
+        if !label.contains(&name) {
+            label.push(name);
+        }
+    }
+
+    label
+}
+
+// This removes any " (X)" from the artist name that discogs appends when there
 // is more than one artist with the same name
-fn process_artist(artist: serde_json::Value) -> String {
-    let mut artist = artist.as_str().unwrap().to_string();
-    if artist.chars().nth(artist.len() - 1).unwrap() == ')' {
-        artist.truncate(artist.len() - 4);
+pub(crate) fn process_artist(artist: &Value) -> String {
+    let artist = artist.as_str().unwrap_or("Unknown Artist");
+    if let Some(start) = artist.rfind(" (") {
+        let suffix = &artist[start + 2..];
+        if let Some(number) = suffix.strip_suffix(')') {
+            if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+                return artist[..start].to_string();
+            }
+        }
     }
-    artist
+    artist.to_string()
 }
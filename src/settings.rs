@@ -0,0 +1,93 @@
+use crate::capabilities::{ColorSupport, GraphicsProtocol};
+use crate::img_to_ascii::{Adjustments, RenderMode};
+use crate::sort::{Grouping, SortOrder};
+
+use serde::{Deserialize, Serialize};
+use std::io::Result;
+use std::path::Path;
+
+const SETTINGS_PATH: &str = "data/settings.json";
+
+// The user's application settings, which are saved whenever they are changed
+// Settings missing from the file, e.g. ones added in a newer version, take their default value
+// discogs_sync: Whether adding and removing records also updates the user's Discogs collection
+// render_mode: How the selected record's cover art is drawn
+// color_support: The colors the cover art is printed in
+// graphics: How the terminal draws the cover image
+// adjustments: The adjustments made to covers when they're converted to AsciiArt, unless a record
+//              has its own
+// sort: The order the records are listed in
+// grouping: What the records list is grouped by, if it's grouped
+// The render mode, colors and graphics are chosen from the terminal's capabilities unless they're
+// set
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub(crate) discogs_sync: bool,
+    pub(crate) render_mode: Option<RenderMode>,
+    pub(crate) color_support: Option<ColorSupport>,
+    pub(crate) graphics: Option<GraphicsProtocol>,
+    pub(crate) adjustments: Adjustments,
+    pub(crate) sort: SortOrder,
+    pub(crate) grouping: Option<Grouping>,
+}
+
+impl Settings {
+    // Loads the settings from their data file, or the default settings if there isn't one
+    pub fn load() -> Result<Settings> {
+        if Path::new(SETTINGS_PATH).exists() {
+            let data_string = std::fs::read_to_string(SETTINGS_PATH)?;
+            Ok(serde_json::from_str(data_string.as_str())?)
+        } else {
+            Ok(Settings::default())
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let data_string = serde_json::to_string_pretty(self)?;
+        std::fs::write(SETTINGS_PATH, data_string)
+    }
+}
+
+// Parses the value of a setting that can be left to be chosen automatically, where "auto" is
+// None
+pub fn parse_auto<T>(value: &str, parse: fn(&str) -> Option<T>) -> Option<Option<T>> {
+    match value.to_lowercase().as_str() {
+        "auto" => Some(None),
+        _ => parse(value).map(Some),
+    }
+}
+
+// The names an on/off setting value can be written as
+pub const SWITCH_NAMES: [(&str, bool); 6] = [
+    ("on", true),
+    ("off", false),
+    ("true", true),
+    ("false", false),
+    ("yes", true),
+    ("no", false),
+];
+
+// Parses an on/off setting value
+pub fn parse_switch(value: &str) -> Option<bool> {
+    parse_name(value, &SWITCH_NAMES)
+}
+
+// Parses a setting value from the names its values can be written as, ignoring case
+pub fn parse_name<T: Copy>(value: &str, names: &[(&str, T)]) -> Option<T> {
+    names
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, value)| *value)
+}
+
+// The names a setting's values are shown as, e.g. in completions and help, which are the first
+// name of each value, leaving out the other ways it can be written
+pub fn value_names<T: PartialEq>(names: &[(&'static str, T)]) -> Vec<&'static str> {
+    names
+        .iter()
+        .enumerate()
+        .filter(|(i, (_, value))| !names[..*i].iter().any(|(_, earlier)| earlier == value))
+        .map(|(_, (name, _))| *name)
+        .collect()
+}
@@ -0,0 +1,264 @@
+use crate::record::{Record, RecordCollection};
+use crate::settings::parse_name;
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use uuid::Uuid;
+
+// The articles that artists' names are sorted without, so that "The Beatles" is listed under B
+const ARTICLES: [&str; 3] = ["the ", "a ", "an "];
+
+// What the records list can be sorted by
+// Artist: The record's first artist, without a leading article
+// Added: When the record was added to the collection, where records from before this was saved
+//        are listed last
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Artist,
+    Title,
+    Year,
+    Added,
+    Genre,
+    Country,
+}
+
+// The order the records are listed in, by a key in either direction
+// Records with the same key are listed by artist, then title, then year, and records missing the
+// key, e.g. ones without a year, are listed last whichever direction they're sorted in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SortOrder {
+    pub(crate) key: SortKey,
+    pub(crate) descending: bool,
+}
+
+impl SortKey {
+    // The names the sort keys are written as in the settings
+    pub const NAMES: [(&'static str, SortKey); 7] = [
+        ("artist", SortKey::Artist),
+        ("title", SortKey::Title),
+        ("year", SortKey::Year),
+        ("added", SortKey::Added),
+        ("genre", SortKey::Genre),
+        ("country", SortKey::Country),
+        ("date", SortKey::Added),
+    ];
+}
+
+impl SortOrder {
+    // The names of the directions a sort order can be in, where true is descending
+    pub const DIRECTIONS: [(&'static str, bool); 4] = [
+        ("asc", false),
+        ("desc", true),
+        ("ascending", false),
+        ("descending", true),
+    ];
+
+    // Parses a sort order from its key and an optional direction, e.g. "year desc" or "title"
+    pub fn parse(value: &str) -> Option<SortOrder> {
+        let mut words = value.split_whitespace();
+        let key = parse_name(words.next()?, &SortKey::NAMES)?;
+        let descending = match words.next() {
+            Some(direction) => parse_name(direction, &SortOrder::DIRECTIONS)?,
+            None => false,
+        };
+
+        words
+            .next()
+            .is_none()
+            .then_some(SortOrder { key, descending })
+    }
+
+    // Compares two records in the order they're listed in
+    pub fn compare(&self, a: &Record, b: &Record) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Artist => sort_name(a.artist()).cmp(&sort_name(b.artist())),
+            SortKey::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortKey::Year => missing_last(a.year, b.year, self.descending),
+            SortKey::Added => missing_last(a.added, b.added, self.descending),
+            SortKey::Genre => missing_last(first_genre(a), first_genre(b), self.descending),
+            SortKey::Country => missing_last(
+                a.country.to_lowercase(),
+                b.country.to_lowercase(),
+                self.descending,
+            ),
+        };
+        let ordering = if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+
+        ordering.then_with(|| tiebreak(a, b))
+    }
+}
+
+// What the records list can be grouped by, with a collapsible header above each group
+// Decade: The decade the record was released in, e.g. "1970s"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grouping {
+    Artist,
+    Genre,
+    Decade,
+}
+
+impl Grouping {
+    // The names of what the list can be grouped by as they're written in the settings
+    pub const NAMES: [(&'static str, Grouping); 3] = [
+        ("artist", Grouping::Artist),
+        ("genre", Grouping::Genre),
+        ("decade", Grouping::Decade),
+    ];
+
+    pub fn parse(name: &str) -> Option<Grouping> {
+        parse_name(name, &Grouping::NAMES)
+    }
+
+    // The name of the group a record is listed in, as it's shown in the group's header
+    pub fn group(&self, record: &Record) -> String {
+        match self {
+            Grouping::Artist => record.artist().to_string(),
+            Grouping::Genre => record
+                .genre
+                .first()
+                .cloned()
+                .unwrap_or_else(|| "No Genre".to_string()),
+            Grouping::Decade if record.year == 0 => "Unknown Year".to_string(),
+            Grouping::Decade => format!("{}s", record.year / 10 * 10),
+        }
+    }
+
+    // What records are grouped by, which for artists is the name they're sorted by, so that e.g.
+    // "The Beatles" and "Beatles" are listed together under the name of the first of them
+    pub fn key(&self, record: &Record) -> String {
+        match self {
+            Grouping::Artist => sort_name(record.artist()),
+            _ => self.group(record),
+        }
+    }
+
+    // Compares the groups two records are listed in
+    // Groups are listed in ascending order, unless the list is sorted in descending order by what
+    // it's grouped by, e.g. decades go from newest to oldest when the records are sorted by
+    // descending year. Records without a genre or year are grouped last
+    fn compare(&self, a: &Record, b: &Record, order: &SortOrder) -> Ordering {
+        let descending = order.descending
+            && matches!(
+                (self, order.key),
+                (Grouping::Artist, SortKey::Artist)
+                    | (Grouping::Genre, SortKey::Genre)
+                    | (Grouping::Decade, SortKey::Year)
+            );
+        let ordering = match self {
+            Grouping::Artist => sort_name(a.artist()).cmp(&sort_name(b.artist())),
+            Grouping::Genre => missing_last(first_genre(a), first_genre(b), descending),
+            Grouping::Decade => missing_last(a.year / 10, b.year / 10, descending),
+        };
+
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+// Sorts a list of record IDs in the order the records are listed in, with the records of each
+// group together when the list is grouped
+pub fn sort_records(
+    collection: &RecordCollection,
+    ids: &mut [Uuid],
+    order: &SortOrder,
+    grouping: Option<Grouping>,
+) {
+    ids.sort_by(|a, b| {
+        let (a, b) = (&collection[a], &collection[b]);
+        let group = match grouping {
+            Some(grouping) => grouping.compare(a, b, order),
+            None => Ordering::Equal,
+        };
+        group.then_with(|| order.compare(a, b))
+    });
+}
+
+// The name an artist is sorted by, which ignores case and a leading article, e.g. "The Beatles"
+// is sorted as "beatles"
+pub fn sort_name(name: &str) -> String {
+    let name = name.to_lowercase();
+    match ARTICLES
+        .iter()
+        .find_map(|article| name.strip_prefix(article))
+    {
+        Some(rest) if !rest.is_empty() => rest.to_string(),
+        _ => name,
+    }
+}
+
+// Orders records with the same sort key by artist, then title, then year
+fn tiebreak(a: &Record, b: &Record) -> Ordering {
+    sort_name(a.artist())
+        .cmp(&sort_name(b.artist()))
+        .then_with(|| a.title.to_lowercase().cmp(&b.title.to_lowercase()))
+        .then_with(|| a.year.cmp(&b.year))
+}
+
+// A record's first genre, used to sort and group it
+fn first_genre(record: &Record) -> String {
+    record
+        .genre
+        .first()
+        .map_or(String::new(), |genre| genre.to_lowercase())
+}
+
+// Compares two values where the default value, e.g. an empty string or a year of 0, means it's
+// missing. Missing values are ordered last, and stay last once the ordering is reversed for a
+// descending sort
+fn missing_last<T: Ord + Default>(a: T, b: T, descending: bool) -> Ordering {
+    let missing = |value: &T| *value == T::default();
+    match (missing(&a), missing(&b)) {
+        (true, true) => Ordering::Equal,
+        (true, false) if descending => Ordering::Less,
+        (true, false) => Ordering::Greater,
+        (false, true) if descending => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.cmp(&b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(artist: &str, added: u64) -> Record {
+        Record {
+            id: Uuid::new_v4(),
+            title: format!("{} {}", artist, added),
+            artists: vec![artist.to_string()],
+            added,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lists_records_without_an_added_date_last() {
+        let (old, new, unknown) = (record("A", 1), record("B", 2), record("C", 0));
+        for descending in [false, true] {
+            let order = SortOrder {
+                key: SortKey::Added,
+                descending,
+            };
+            assert_eq!(order.compare(&old, &unknown), Ordering::Less);
+            assert_eq!(order.compare(&new, &unknown), Ordering::Less);
+        }
+    }
+
+    #[test]
+    fn groups_artists_by_their_sort_name() {
+        let (the, bare) = (record("The Beatles", 1), record("beatles", 2));
+        assert_eq!(Grouping::Artist.key(&the), Grouping::Artist.key(&bare));
+        assert_eq!(Grouping::Artist.group(&the), "The Beatles");
+    }
+}
@@ -0,0 +1,419 @@
+use crate::discogs_client::CollectionInstance;
+use crate::record::{Record, RecordCollection};
+use crate::sort::{sort_records, SortOrder};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{Result, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+const COLLECTION_PATH: &str = "data/collection.json";
+const BACKUPS_PATH: &str = "data/backups";
+// The number of backups kept, after which the oldest ones are deleted
+const MAX_BACKUPS: usize = 10;
+// Saving makes a new backup when the latest one is older than this many seconds
+const BACKUP_INTERVAL: u64 = 10 * 60;
+// Records that couldn't be read are moved here so that they aren't lost when the collection is
+// next saved
+const UNREADABLE_PATH: &str = "data/collection_unreadable.json";
+// The Discogs copies of records removed from the collection without removing them from Discogs,
+// kept so that syncing can offer to remove them from Discogs too
+const REMOVED_INSTANCES_PATH: &str = "data/removed_instances.json";
+
+// The version of the collection file format written by this version of the app
+// Version 1: A bare list of records
+// Version 2: A CollectionFile envelope holding the records along with the file's metadata
+// Version 3: Records' AsciiArt is saved in a compact form
+// Version 4: Records have the time they were added to the collection
+const CURRENT_VERSION: u64 = 4;
+
+// The migrations that upgrade the collection file's json data from one version to the next, where
+// MIGRATIONS[i] upgrades version i + 1 to version i + 2
+const MIGRATIONS: [fn(Value) -> Value; (CURRENT_VERSION - 1) as usize] =
+    [migrate_v1_to_v2, migrate_v2_to_v3, migrate_v3_to_v4];
+
+// The top level of the collection file
+// version: The format version the file was written in
+// metadata: Information about when and by what the file was written
+// records: The collection's records, which are deserialized one by one when loading so that a
+//          single unreadable record doesn't prevent the rest from loading
+#[derive(Serialize, Deserialize)]
+struct CollectionFile {
+    version: u64,
+    #[serde(default)]
+    metadata: Metadata,
+    #[serde(default)]
+    records: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Metadata {
+    app_version: String,
+    saved_at: u64,
+    record_count: usize,
+}
+
+// Loads the user's collection from the serialized data file into a RecordCollection object
+// Files written by older versions of the app are migrated to the current format, and a copy of the
+// original file is kept alongside it
+pub fn load_collection() -> Result<RecordCollection> {
+    if !Path::new(COLLECTION_PATH).exists() {
+        return Ok(HashMap::new());
+    }
+
+    load_collection_from(COLLECTION_PATH)
+}
+
+// Loads a collection from the given data file, migrating it if it's from an older version
+fn load_collection_from(path: &str) -> Result<RecordCollection> {
+    let mut collection: RecordCollection = HashMap::new();
+    let data_string = std::fs::read_to_string(path)?;
+    let mut data: Value = serde_json::from_str(data_string.as_str())?;
+
+    let version = file_version(&data);
+    if version > CURRENT_VERSION {
+        return Err(std::io::Error::other(format!(
+            "{} was written by a newer version of Vinylla (format version {})",
+            path, version
+        )));
+    }
+    if version == 0 {
+        return Err(std::io::Error::other(format!(
+            "{} has an invalid format version of 0",
+            path
+        )));
+    }
+    if version < CURRENT_VERSION {
+        // Keeps the original of a migrated collection file in case the migration goes wrong
+        let original_path = format!("data/collection.v{}.json", version);
+        if path == COLLECTION_PATH && !Path::new(&original_path).exists() {
+            std::fs::write(original_path, &data_string)?;
+        }
+        data = migrate(data, version);
+    }
+
+    let file: CollectionFile = serde_json::from_value(data)?;
+    let mut unreadable = Vec::new();
+    for value in file.records {
+        match serde_json::from_value::<Record>(value.clone()) {
+            Ok(record) => {
+                collection.insert(record.id, record);
+            }
+            Err(_) => unreadable.push(value),
+        }
+    }
+
+    if !unreadable.is_empty() {
+        save_unreadable(unreadable)?;
+    }
+
+    Ok(collection)
+}
+
+// Writes the collection to its data file in the current format, with the records in the app's
+// default order
+// The file is replaced atomically, so a crash while saving leaves the previous save intact, and the
+// previous save is backed up if the latest backup is older than BACKUP_INTERVAL
+pub fn save_collection(collection: &RecordCollection) -> Result<()> {
+    let latest_backup = list_backups()?.first().map_or(0, |backup| backup.timestamp);
+    if timestamp().saturating_sub(latest_backup) > BACKUP_INTERVAL {
+        backup_collection()?;
+    }
+
+    let mut ids: Vec<Uuid> = collection.keys().copied().collect();
+    sort_records(collection, &mut ids, &SortOrder::default(), None);
+
+    let records = ids
+        .iter()
+        .map(|id| serde_json::to_value(&collection[id]))
+        .collect::<serde_json::Result<Vec<Value>>>()?;
+
+    let file = CollectionFile {
+        version: CURRENT_VERSION,
+        metadata: Metadata {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            saved_at: timestamp(),
+            record_count: records.len(),
+        },
+        records,
+    };
+
+    let collection_string = serde_json::to_string(&file)?;
+    write_atomic(COLLECTION_PATH, &collection_string)
+}
+
+// A backup of the collection file, named after the time it was made
+// timestamp: The Unix time the backup was made at
+#[derive(Clone, Debug)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub timestamp: u64,
+}
+
+impl Backup {
+    // Counts the records in the backup without deserializing them, returning None if it can't be
+    // read. This reads the whole file, so it's only done when listing the backups to restore
+    pub fn record_count(&self) -> Option<usize> {
+        let data: Value = serde_json::from_str(&std::fs::read_to_string(&self.path).ok()?).ok()?;
+        match data {
+            Value::Array(records) => Some(records.len()),
+            _ => data["records"].as_array().map(|records| records.len()),
+        }
+    }
+}
+
+// Copies the current collection file into the backups folder, deleting the oldest backups past
+// MAX_BACKUPS
+pub fn backup_collection() -> Result<()> {
+    if !Path::new(COLLECTION_PATH).exists() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(BACKUPS_PATH)?;
+    let backup_path = Path::new(BACKUPS_PATH).join(format!("collection-{}.json", timestamp()));
+    std::fs::copy(COLLECTION_PATH, backup_path)?;
+
+    for backup in list_backups()?.iter().skip(MAX_BACKUPS) {
+        std::fs::remove_file(&backup.path)?;
+    }
+
+    Ok(())
+}
+
+// Lists the collection's backups, newest first, from their file names
+pub fn list_backups() -> Result<Vec<Backup>> {
+    if !Path::new(BACKUPS_PATH).exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(BACKUPS_PATH)? {
+        let path = entry?.path();
+        // Backups are named "collection-<timestamp>.json"
+        let timestamp = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("collection-"))
+            .and_then(|name| name.strip_suffix(".json"))
+            .and_then(|timestamp| timestamp.parse().ok());
+
+        if let Some(timestamp) = timestamp {
+            backups.push(Backup { path, timestamp });
+        }
+    }
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.timestamp));
+
+    Ok(backups)
+}
+
+// Loads the collection from a backup
+// The collection is backed up first so that the restore itself can be undone
+pub fn restore_backup(backup: &Backup) -> Result<RecordCollection> {
+    let collection = load_collection_from(&backup.path.to_string_lossy())?;
+    backup_collection()?;
+
+    Ok(collection)
+}
+
+// Writes a file by writing to a temporary file first and renaming it into place, so that the
+// file is never left half written
+fn write_atomic(path: &str, contents: &str) -> Result<()> {
+    let temp_path = format!("{}.tmp", path);
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(temp_path, path)
+}
+
+// The current Unix time in seconds
+pub(crate) fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// Formats a Unix time as a UTC date and time, e.g. "2022-08-14 16:03"
+pub fn format_timestamp(timestamp: u64) -> String {
+    // Converts the days since the epoch into a civil date, following Howard Hinnant's algorithm
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (timestamp / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let seconds_of_day = timestamp % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60
+    )
+}
+
+// Finds the format version of the collection file's json data
+// Files from before the format was versioned are a bare list of records
+fn file_version(data: &Value) -> u64 {
+    match data {
+        Value::Array(_) => 1,
+        _ => data["version"].as_u64().unwrap_or(1),
+    }
+}
+
+// Upgrades the collection file's json data from the given version, from 1 up, to CURRENT_VERSION
+fn migrate(mut data: Value, version: u64) -> Value {
+    for migration in MIGRATIONS[(version - 1) as usize..].iter() {
+        data = migration(data);
+    }
+    data
+}
+
+// Wraps the bare list of records in the versioned envelope
+fn migrate_v1_to_v2(data: Value) -> Value {
+    json!({
+        "version": 2,
+        "metadata": {},
+        "records": data,
+    })
+}
+
+// AsciiArt in the older format is still deserialized, and is converted when the collection is next
+// saved, so only the version changes
+fn migrate_v2_to_v3(mut data: Value) -> Value {
+    data["version"] = json!(3);
+    data
+}
+
+// Records weren't dated when they were added, so they're given the time the file was last saved,
+// which they were added before. Files from before that was saved leave them undated
+fn migrate_v3_to_v4(mut data: Value) -> Value {
+    let saved_at = data["metadata"]["saved_at"].as_u64().unwrap_or(0);
+    if let Some(records) = data["records"].as_array_mut() {
+        for record in records.iter_mut().filter(|record| record.is_object()) {
+            record["added"] = json!(saved_at);
+        }
+    }
+    data["version"] = json!(4);
+    data
+}
+
+// Loads the Discogs copies of records that were removed from the collection but are still in the
+// user's Discogs collection
+pub(crate) fn load_removed_instances() -> Result<Vec<CollectionInstance>> {
+    if !Path::new(REMOVED_INSTANCES_PATH).exists() {
+        return Ok(Vec::new());
+    }
+
+    let data_string = std::fs::read_to_string(REMOVED_INSTANCES_PATH)?;
+    Ok(serde_json::from_str(data_string.as_str())?)
+}
+
+pub(crate) fn save_removed_instances(instances: &[CollectionInstance]) -> Result<()> {
+    write_atomic(REMOVED_INSTANCES_PATH, &serde_json::to_string(instances)?)
+}
+
+// Adds records that couldn't be deserialized to the unreadable records file
+fn save_unreadable(mut records: Vec<Value>) -> Result<()> {
+    if Path::new(UNREADABLE_PATH).exists() {
+        let data_string = std::fs::read_to_string(UNREADABLE_PATH)?;
+        let mut saved: Vec<Value> = serde_json::from_str(data_string.as_str())?;
+        saved.append(&mut records);
+        records = saved;
+    }
+
+    std::fs::write(UNREADABLE_PATH, serde_json::to_string(&records)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A record's json data as older versions wrote it, without the fields added since
+    fn old_record() -> Value {
+        json!({ "title": "Pink Moon", "artists": ["Nick Drake"] })
+    }
+
+    fn migrated_records(data: Value, version: u64) -> Vec<Value> {
+        let data = migrate(data, version);
+        assert_eq!(file_version(&data), CURRENT_VERSION);
+        let file: CollectionFile = serde_json::from_value(data).unwrap();
+        file.records
+    }
+
+    #[test]
+    fn migrates_v1_to_current() {
+        let data = json!([old_record(), old_record()]);
+        assert_eq!(file_version(&data), 1);
+
+        let records = migrated_records(data, 1);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["title"], "Pink Moon");
+        assert_eq!(records[0]["added"], 0);
+    }
+
+    #[test]
+    fn migrates_v2_to_current() {
+        let data = json!({
+            "version": 2,
+            "metadata": { "saved_at": 1660492980 },
+            "records": [old_record()],
+        });
+
+        let records = migrated_records(data, 2);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["added"], 1660492980);
+    }
+
+    #[test]
+    fn migrates_v3_to_current() {
+        let data = json!({
+            "version": 3,
+            "metadata": { "saved_at": 1660492980 },
+            "records": [old_record()],
+        });
+
+        let records = migrated_records(data, 3);
+        assert_eq!(records[0]["artists"][0], "Nick Drake");
+        assert_eq!(records[0]["added"], 1660492980);
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(1660492980), "2022-08-14 16:03");
+        // A leap day, and the day after a leap day in a year divisible by 400
+        assert_eq!(format_timestamp(1582979640), "2020-02-29 12:34");
+        assert_eq!(format_timestamp(951868800), "2000-03-01 00:00");
+        // Past the 32 bit Unix time limit, and in a year divisible by 100 that isn't a leap year
+        assert_eq!(format_timestamp(2147483648), "2038-01-19 03:14");
+        assert_eq!(format_timestamp(4102444800), "2100-01-01 00:00");
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        let path = std::env::temp_dir().join(format!("vinylla-test-{}.json", Uuid::new_v4()));
+        let path = path.to_string_lossy().to_string();
+
+        for version in [0, CURRENT_VERSION + 1] {
+            let data = json!({ "version": version, "records": [] });
+            std::fs::write(&path, data.to_string()).unwrap();
+            assert!(load_collection_from(&path).is_err());
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}